@@ -18,14 +18,133 @@ pub fn cli() -> Command<'static> {
                 .short('c')
                 .long("config")
                 .takes_value(true)
-                .default_value("ellipsis.yml")
-                .help("The configuration file for the operations to perform")
+                .help("The configuration file to use, found automatically if not given")
                 .env("ELLIPSIS"),
         )
+        .arg(
+            Arg::new("tags")
+                .long("tags")
+                .takes_value(true)
+                .value_name("tags")
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .help("Only run the operations with at least one of these comma separated tags"),
+        )
+        .arg(
+            Arg::new("skip-tags")
+                .long("skip-tags")
+                .takes_value(true)
+                .value_name("tags")
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .help("Don't run the operations with any of these comma separated tags"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("Only run the operations tagged with one of the tags in this profile"),
+        )
+        .arg(
+            Arg::new("only")
+                .long("only")
+                .takes_value(true)
+                .value_name("names")
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .help("Only run the operations with these comma separated names"),
+        )
+        .arg(
+            Arg::new("start-at")
+                .long("start-at")
+                .takes_value(true)
+                .value_name("name")
+                .help("Skip the operations before the one with this name"),
+        )
+        .arg(
+            Arg::new("set")
+                .long("set")
+                .takes_value(true)
+                .value_name("name=value")
+                .multiple_occurrences(true)
+                .help("Set a variable, overriding any value given in the config"),
+        )
+        .arg(
+            Arg::new("which-config")
+                .long("which-config")
+                .takes_value(false)
+                .help("Print the configuration file that would be used, then exit"),
+        )
         .arg(
             Arg::new("dry-run")
                 .long("dry-run")
                 .takes_value(false)
                 .help("Print what would be done without making any changes"),
         )
+        .arg(
+            Arg::new("diff")
+                .long("diff")
+                .takes_value(false)
+                .requires("dry-run")
+                .help("Show what each copy and link would change during a dry run"),
+        )
+        .arg(
+            Arg::new("keep-going")
+                .long("keep-going")
+                .takes_value(false)
+                .help("Carry on with the remaining operations when one fails"),
+        )
+        .arg(
+            Arg::new("prune")
+                .long("prune")
+                .takes_value(false)
+                .help("Remove copies and links ellipsis made that are no longer in the config"),
+        )
+        .arg(
+            Arg::new("jobs")
+                .short('j')
+                .long("jobs")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("1")
+                .help("Run up to this many operations that do not need each other at once"),
+        )
+        .subcommand(Command::new("list").about("List the operations that would be run"))
+        .subcommand(
+            Command::new("graph")
+                .about("Print the operations and what they need as a graph in DOT format"),
+        )
+        .subcommand(
+            Command::new("status")
+                .about("Show whether each copy and link target is as ellipsis would leave it")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(["table", "json"])
+                        .default_value("table")
+                        .help("The format to print the statuses in"),
+                ),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Show what each copy and link would change, without changing it"),
+        )
+        .subcommand(
+            Command::new("uninstall")
+                .about("Reverse everything ellipsis has done, putting back what it replaced"),
+        )
+        .subcommand(
+            Command::new("facts")
+                .about("Print the facts gathered about this machine")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(["yaml", "json"])
+                        .default_value("yaml")
+                        .help("The format to print the facts in"),
+                ),
+        )
 }
@@ -0,0 +1,406 @@
+use std::path::PathBuf;
+
+use regex::Regex;
+
+use crate::config::vars::{Vars, ENV_PREFIX};
+use crate::config::Error;
+use crate::domain::Condition;
+
+const FACTS_PREFIX: &str = "facts.";
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Name(String),
+    Text(String),
+    Equals,
+    NotEquals,
+    Matches,
+    NotMatches,
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+enum Value {
+    Text(String),
+    Condition(Condition),
+}
+
+pub fn parse<F>(expression: &str, vars: &Vars, resolve_path: F) -> Result<Condition, Error>
+where
+    F: Fn(&str) -> PathBuf,
+{
+    let mut parser = Parser {
+        expression,
+        tokens: tokenize(expression)?,
+        position: 0,
+        vars,
+        resolve_path,
+    };
+
+    let condition = parser.or()?;
+
+    match parser.next() {
+        None => Ok(condition),
+        Some(token) => Err(parser.error(&format!("unexpected {}", describe(&token)))),
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, Error> {
+    let error = |reason: &str| Error::Condition(expression.into(), reason.into());
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Equals,
+            '=' if chars.next_if_eq(&'~').is_some() => Token::Matches,
+            '!' if chars.next_if_eq(&'=').is_some() => Token::NotEquals,
+            '!' if chars.next_if_eq(&'~').is_some() => Token::NotMatches,
+            '!' => Token::Not,
+            '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+            '"' | '\'' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => text.extend(chars.next()),
+                        Some(end) if end == c => break,
+                        Some(other) => text.push(other),
+                        None => return Err(error("unterminated string")),
+                    }
+                }
+                Token::Text(text)
+            }
+            c if is_name_char(c) => {
+                let mut name = String::from(c);
+                while let Some(c) = chars.next_if(|c| is_name_char(*c)) {
+                    name.push(c);
+                }
+                Token::Name(name)
+            }
+            other => return Err(error(&format!("unexpected `{}`", other))),
+        };
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+const fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Name(name) => format!("`{}`", name),
+        Token::Text(text) => format!("\"{}\"", text),
+        Token::Equals => "`==`".into(),
+        Token::NotEquals => "`!=`".into(),
+        Token::Matches => "`=~`".into(),
+        Token::NotMatches => "`!~`".into(),
+        Token::And => "`&&`".into(),
+        Token::Or => "`||`".into(),
+        Token::Not => "`!`".into(),
+        Token::Open => "`(`".into(),
+        Token::Close => "`)`".into(),
+    }
+}
+
+struct Parser<'a, F> {
+    expression: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+    vars: &'a Vars,
+    resolve_path: F,
+}
+
+impl<'a, F> Parser<'a, F>
+where
+    F: Fn(&str) -> PathBuf,
+{
+    fn error(&self, reason: &str) -> Error {
+        Error::Condition(self.expression.into(), reason.into())
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: &Token) -> Result<(), Error> {
+        match self.next() {
+            Some(token) if &token == expected => Ok(()),
+            Some(token) => Err(self.error(&format!(
+                "expected {} but found {}",
+                describe(expected),
+                describe(&token)
+            ))),
+            None => Err(self.error(&format!("expected {}", describe(expected)))),
+        }
+    }
+
+    fn or(&mut self) -> Result<Condition, Error> {
+        let mut condition = self.and()?;
+
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, Error> {
+        let mut condition = self.unary()?;
+
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            condition = Condition::And(Box::new(condition), Box::new(self.unary()?));
+        }
+
+        Ok(condition)
+    }
+
+    fn unary(&mut self) -> Result<Condition, Error> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.next();
+                Ok(Condition::Not(Box::new(self.unary()?)))
+            }
+            Some(Token::Open) => {
+                self.next();
+                let condition = self.or()?;
+                self.expect(&Token::Close)?;
+                Ok(condition)
+            }
+            _ => self.comparison(),
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Condition, Error> {
+        let left = self.value()?;
+        let operator = match self.peek() {
+            Some(Token::Equals | Token::NotEquals | Token::Matches | Token::NotMatches) => {
+                self.next()
+            }
+            _ => None,
+        };
+
+        let (left, operator) = match (left, operator) {
+            (Value::Condition(condition), None) => return Ok(condition),
+            (Value::Text(text), None) => {
+                return match text.as_str() {
+                    "true" => Ok(Condition::Constant(true)),
+                    "false" => Ok(Condition::Constant(false)),
+                    _ => Err(self.error(&format!("\"{}\" is not true or false", text))),
+                }
+            }
+            (Value::Text(left), Some(operator)) => (left, operator),
+            (Value::Condition(_), Some(operator)) => {
+                return Err(self.error(&format!(
+                    "a function can't be compared with {}",
+                    describe(&operator)
+                )))
+            }
+        };
+
+        let right = match self.value()? {
+            Value::Text(right) => right,
+            Value::Condition(_) => {
+                return Err(self.error(&format!(
+                    "a function can't be compared with {}",
+                    describe(&operator)
+                )))
+            }
+        };
+
+        let holds = match operator {
+            Token::Equals => left == right,
+            Token::NotEquals => left != right,
+            _ => {
+                let pattern = Regex::new(&right)
+                    .map_err(|error| self.error(&format!("invalid pattern: {}", error)))?;
+                pattern.is_match(&left) == (operator == Token::Matches)
+            }
+        };
+
+        Ok(Condition::Constant(holds))
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        match self.next() {
+            Some(Token::Text(text)) => Ok(Value::Text(text)),
+            Some(Token::Name(name)) if self.peek() == Some(&Token::Open) => {
+                self.next();
+                let argument = match self.value()? {
+                    Value::Text(argument) => argument,
+                    Value::Condition(_) => {
+                        return Err(self.error(&format!(
+                            "the argument to `{}` must be a string or variable",
+                            name
+                        )))
+                    }
+                };
+                self.expect(&Token::Close)?;
+
+                match name.as_str() {
+                    "command_exists" => Ok(Value::Condition(Condition::CommandExists(argument))),
+                    "path_exists" => Ok(Value::Condition(Condition::PathExists((self
+                        .resolve_path)(
+                        &argument
+                    )))),
+                    _ => Err(self.error(&format!("unknown function `{}`", name))),
+                }
+            }
+            Some(Token::Name(name)) => self
+                .vars
+                .get(&name)
+                .or_else(|| self.vars.get(&format!("{}{}", FACTS_PREFIX, name)))
+                .map(|value| Value::Text(value.into()))
+                .or_else(|| {
+                    name.starts_with(ENV_PREFIX)
+                        .then(|| Value::Text(String::new()))
+                })
+                .ok_or(Error::UndefinedVariable(name)),
+            Some(token) => Err(self.error(&format!(
+                "expected a string, variable or function but found {}",
+                describe(&token)
+            ))),
+            None => Err(self.error("expected a string, variable or function")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+
+    use super::parse;
+    use crate::config::vars::Vars;
+    use crate::config::Error;
+    use crate::domain::Condition;
+
+    fn vars() -> Vars {
+        Vars::new(
+            BTreeMap::from([
+                ("facts.os".into(), "linux".into()),
+                ("facts.hostname".into(), "work-laptop".into()),
+                ("facts.container".into(), "false".into()),
+                ("role".into(), "server".into()),
+            ]),
+            BTreeMap::from([("TERM".into(), "xterm".into())]),
+        )
+    }
+
+    fn condition(expression: &str) -> Result<Condition, Error> {
+        parse(expression, &vars(), |path| Path::new("/base").join(path))
+    }
+
+    #[test]
+    fn comparisons_are_evaluated_against_facts_and_vars() {
+        assert_eq!(
+            Condition::And(
+                Box::new(Condition::Constant(true)),
+                Box::new(Condition::Constant(true))
+            ),
+            condition(r#"os == "linux" && hostname =~ "^work-""#).unwrap()
+        );
+        assert_eq!(
+            Condition::Or(
+                Box::new(Condition::Constant(false)),
+                Box::new(Condition::Constant(true))
+            ),
+            condition(r#"facts.os != 'linux' || env.TERM !~ "^dumb$""#).unwrap()
+        );
+        assert_eq!(
+            Condition::Constant(false),
+            condition(r#"role == "laptop""#).unwrap()
+        );
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            Condition::Or(
+                Box::new(Condition::Constant(true)),
+                Box::new(Condition::And(
+                    Box::new(Condition::Constant(false)),
+                    Box::new(Condition::Not(Box::new(Condition::Constant(false))))
+                ))
+            ),
+            condition(r#"os == "linux" || (container) && !container"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn functions_are_left_for_later() {
+        assert_eq!(
+            Condition::And(
+                Box::new(Condition::CommandExists("brew".into())),
+                Box::new(Condition::Not(Box::new(Condition::PathExists(
+                    PathBuf::from("/base/.ssh")
+                ))))
+            ),
+            condition(r#"command_exists("brew") && !path_exists(".ssh")"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn unset_environment_variables_are_empty() {
+        assert_eq!(
+            Condition::And(
+                Box::new(Condition::Constant(false)),
+                Box::new(Condition::Constant(true))
+            ),
+            condition(r#"env.CI_DEFINITELY_UNSET == "true" && env.CI_DEFINITELY_UNSET == """#)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn undefined_names_are_an_error() {
+        assert!(matches!(
+            condition(r#"shell == "zsh""#),
+            Err(Error::UndefinedVariable(name)) if name == "shell"
+        ));
+    }
+
+    #[test]
+    fn invalid_conditions_are_an_error() {
+        for (expression, reason) in [
+            (r#"os == "linux"#, "unterminated string"),
+            (r#"os = "linux""#, "unexpected `=`"),
+            (r#"os == "linux" ("#, "unexpected `(`"),
+            (r#"(os == "linux""#, "expected `)`"),
+            (r#"os"#, "\"linux\" is not true or false"),
+            (r#"exists("brew")"#, "unknown function `exists`"),
+            (r#"os =~ "(""#, "invalid pattern"),
+        ] {
+            match condition(expression) {
+                Err(Error::Condition(actual, message)) => {
+                    assert_eq!(expression, actual);
+                    assert!(
+                        message.starts_with(reason),
+                        "`{}` should start with `{}`",
+                        message,
+                        reason
+                    );
+                }
+                other => panic!("{} should be invalid, got {:?}", expression, other),
+            }
+        }
+    }
+}
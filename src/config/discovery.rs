@@ -0,0 +1,78 @@
+use std::path::{Path, PathBuf};
+
+const FILE_NAMES: [&str; 2] = ["ellipsis.yml", ".ellipsis.yml"];
+
+pub fn find(start: &Path, config_home: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .flat_map(|directory| FILE_NAMES.iter().map(move |name| directory.join(name)))
+        .chain(std::iter::once(
+            config_home.join("ellipsis").join("ellipsis.yml"),
+        ))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::find;
+
+    #[test]
+    fn finds_config_in_the_start_directory() {
+        let root = tempfile::tempdir().unwrap().into_path();
+        fs::write(root.join("ellipsis.yml"), "").unwrap();
+
+        assert_eq!(
+            Some(root.join("ellipsis.yml")),
+            find(&root, &root.join(".config"))
+        );
+    }
+
+    #[test]
+    fn finds_hidden_config_in_a_parent_directory() {
+        let root = tempfile::tempdir().unwrap().into_path();
+        let nested = root.join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join(".ellipsis.yml"), "").unwrap();
+
+        assert_eq!(
+            Some(root.join(".ellipsis.yml")),
+            find(&nested, &root.join(".config"))
+        );
+    }
+
+    #[test]
+    fn nearest_config_wins() {
+        let root = tempfile::tempdir().unwrap().into_path();
+        let nested = root.join("a");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join("ellipsis.yml"), "").unwrap();
+        fs::write(nested.join(".ellipsis.yml"), "").unwrap();
+
+        assert_eq!(
+            Some(nested.join(".ellipsis.yml")),
+            find(&nested, &root.join(".config"))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_config_home() {
+        let root = tempfile::tempdir().unwrap().into_path();
+        let config_home = tempfile::tempdir().unwrap().into_path();
+        fs::create_dir_all(config_home.join("ellipsis")).unwrap();
+        fs::write(config_home.join("ellipsis").join("ellipsis.yml"), "").unwrap();
+
+        assert_eq!(
+            Some(config_home.join("ellipsis").join("ellipsis.yml")),
+            find(&root, &config_home)
+        );
+    }
+
+    #[test]
+    fn nothing_found() {
+        let root = tempfile::tempdir().unwrap().into_path();
+
+        assert_eq!(None, find(&root, &root.join(".config")));
+    }
+}
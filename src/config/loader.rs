@@ -0,0 +1,427 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::vars::Vars;
+use crate::config::{location, parse_when, ConfigOperation, ConfigOuter, ConfigTodo, Error};
+use crate::domain;
+
+pub struct Loader<'a> {
+    home: &'a str,
+    current_dir: &'a Path,
+    overrides: BTreeMap<String, String>,
+    including: Vec<PathBuf>,
+    pub profiles: BTreeMap<String, Vec<String>>,
+    pub steps: Vec<domain::Step>,
+}
+
+impl<'a> Loader<'a> {
+    pub const fn new(
+        home: &'a str,
+        current_dir: &'a Path,
+        overrides: BTreeMap<String, String>,
+    ) -> Self {
+        Self {
+            home,
+            current_dir,
+            overrides,
+            including: Vec::new(),
+            profiles: BTreeMap::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn load(&mut self, file: &Path, vars: &Vars) -> Result<(), Error> {
+        let canonical = fs::canonicalize(self.current_dir.join(file))
+            .map_err(|error| Error::Read(file.to_path_buf(), error))?;
+
+        if self.including.contains(&canonical) {
+            let mut cycle = self.including.clone();
+            cycle.push(canonical);
+            return Err(Error::IncludeCycle(cycle));
+        }
+
+        self.including.push(canonical);
+        self.load_file(file, vars)?;
+        self.including.pop();
+
+        Ok(())
+    }
+
+    fn load_file(&mut self, file: &Path, vars: &Vars) -> Result<(), Error> {
+        let config = fs::read_to_string(self.current_dir.join(file))
+            .map_err(|error| Error::Read(file.to_path_buf(), error))?;
+        let outer: ConfigOuter = serde_yaml::from_str(&config)
+            .map_err(|error| Error::YamlParse(file.to_path_buf(), error))?;
+        let positions = location::todo_positions(&config);
+        let vars = vars
+            .clone()
+            .overridden_by(&self.overrides)
+            .with(outer.vars)
+            .map_err(|error| Error::Vars(file.to_path_buf(), Box::new(error)))?
+            .overridden_by(&self.overrides);
+        let config_dir = self
+            .current_dir
+            .join(file.parent().unwrap_or_else(|| Path::new("")));
+        let base_dir = match &outer.base_dir {
+            Some(base_dir) => {
+                domain::OperationPath::new(&config_dir, Path::new(self.home), base_dir).location
+            }
+            None => config_dir.clone(),
+        };
+
+        self.profiles.extend(outer.profiles);
+
+        for pattern in &outer.include {
+            self.include(&config_dir, pattern, &vars)?;
+        }
+
+        for (index, todo) in outer.todo.into_iter().enumerate() {
+            let origin = domain::Origin {
+                file: file.to_path_buf(),
+                index: index + 1,
+                position: positions.get(index).copied(),
+            };
+
+            match &todo.operation {
+                ConfigOperation::Include(pattern) => self
+                    .include_entry(&config_dir, &base_dir, pattern, &todo, &vars)
+                    .map_err(|error| Error::Todo(origin, Box::new(error)))?,
+                _ => {
+                    let default_name = (self.steps.len() + 1).to_string();
+                    self.steps.push(todo.into_step(
+                        self.home,
+                        &base_dir,
+                        &vars,
+                        default_name,
+                        origin,
+                    )?);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn include_entry(
+        &mut self,
+        config_dir: &Path,
+        base_dir: &Path,
+        pattern: &str,
+        todo: &ConfigTodo,
+        vars: &Vars,
+    ) -> Result<(), Error> {
+        if let Some(name) = &todo.name {
+            return Err(Error::IncludeName(name.clone()));
+        }
+
+        let first = self.steps.len();
+        self.include(config_dir, pattern, vars)?;
+
+        let when = todo
+            .when
+            .clone()
+            .map(|expression| parse_when(expression, self.home, base_dir, vars))
+            .transpose()?;
+
+        for step in &mut self.steps[first..] {
+            step.tags.extend(todo.tags.iter().cloned());
+            step.needs.extend(todo.needs.iter().cloned());
+            step.ignore_errors |= todo.ignore_errors;
+
+            if let Some(when) = &when {
+                step.when = Some(match step.when.take() {
+                    Some(inner) => when.and(inner),
+                    None => when.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn include(&mut self, config_dir: &Path, pattern: &str, vars: &Vars) -> Result<(), Error> {
+        let pattern = vars.interpolate(pattern)?;
+        let pattern =
+            domain::OperationPath::new(config_dir, Path::new(self.home), &pattern).location;
+        let pattern = pattern.to_string_lossy();
+
+        if glob::Pattern::escape(&pattern) == pattern {
+            return self.load(Path::new(pattern.as_ref()), vars);
+        }
+
+        let mut files = glob::glob(&pattern)
+            .map_err(|error| Error::IncludePattern(pattern.to_string(), error))?
+            .map(|file| file.map_err(|error| Error::Read(error.path().to_path_buf(), error.into())))
+            .collect::<Result<Vec<_>, _>>()?;
+        files.sort();
+
+        for file in files {
+            self.load(&file, vars)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::Path;
+
+    use indoc::indoc;
+
+    use super::Loader;
+    use crate::config::vars::Vars;
+    use crate::config::Error;
+    use crate::domain::{Condition, Operation, When};
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn load(root: &Path) -> Result<Vec<Operation>, Error> {
+        let mut loader = Loader::new("/home/user", root, BTreeMap::new());
+        loader.load(Path::new("ellipsis.yml"), &Vars::default())?;
+
+        Ok(loader
+            .steps
+            .into_iter()
+            .map(|step| step.operation)
+            .collect())
+    }
+
+    fn copy(working_dir: &Path, from: &str) -> Operation {
+        Operation::new_copy("/home/user", working_dir, "~/out.txt", from)
+    }
+
+    #[test]
+    fn includes_globs_in_order_before_the_todo_list() {
+        let root = tempfile::tempdir().unwrap().into_path();
+        write(
+            &root.join("ellipsis.yml"),
+            indoc! {r#"
+            ---
+            include:
+            - machines/*.yml
+            todo:
+            - copy:
+                from: main.txt
+                to: ~/out.txt
+        "#},
+        );
+        for name in ["b", "a"] {
+            write(
+                &root.join("machines").join(format!("{}.yml", name)),
+                &format!(
+                    "todo:\n- copy:\n    from: {}.txt\n    to: ~/out.txt\n",
+                    name
+                ),
+            );
+        }
+
+        assert_eq!(
+            vec![
+                copy(&root.join("machines"), "a.txt"),
+                copy(&root.join("machines"), "b.txt"),
+                copy(&root, "main.txt"),
+            ],
+            load(&root).unwrap()
+        );
+    }
+
+    #[test]
+    fn includes_as_todo_entries_resolve_against_the_including_file() {
+        let root = tempfile::tempdir().unwrap().into_path();
+        write(
+            &root.join("ellipsis.yml"),
+            indoc! {r#"
+            ---
+            todo:
+            - copy:
+                from: first.txt
+                to: ~/out.txt
+            - include: shell/ellipsis.yml
+            - copy:
+                from: last.txt
+                to: ~/out.txt
+        "#},
+        );
+        write(
+            &root.join("shell").join("ellipsis.yml"),
+            "todo:\n- include: zsh.yml\n",
+        );
+        write(
+            &root.join("shell").join("zsh.yml"),
+            "todo:\n- copy:\n    from: zshrc\n    to: ~/out.txt\n",
+        );
+
+        assert_eq!(
+            vec![
+                copy(&root, "first.txt"),
+                copy(&root.join("shell"), "zshrc"),
+                copy(&root, "last.txt"),
+            ],
+            load(&root).unwrap()
+        );
+    }
+
+    #[test]
+    fn included_files_inherit_vars() {
+        let root = tempfile::tempdir().unwrap().into_path();
+        write(
+            &root.join("ellipsis.yml"),
+            "vars: { machine: laptop }\ninclude: [\"${machine}.yml\"]\n",
+        );
+        write(
+            &root.join("laptop.yml"),
+            "todo:\n- copy:\n    from: ${machine}.txt\n    to: ~/out.txt\n",
+        );
+
+        assert_eq!(vec![copy(&root, "laptop.txt")], load(&root).unwrap());
+    }
+
+    #[test]
+    fn when_on_an_include_applies_to_every_included_step() {
+        let root = tempfile::tempdir().unwrap().into_path();
+        write(
+            &root.join("ellipsis.yml"),
+            "vars: { os: linux }\ntodo:\n- include: linux.yml\n  when: os == \"linux\"\n",
+        );
+        write(
+            &root.join("linux.yml"),
+            indoc! {r#"
+            todo:
+            - copy:
+                from: a.txt
+                to: ~/out.txt
+            - copy:
+                from: b.txt
+                to: ~/out.txt
+              when: command_exists("apt")
+        "#},
+        );
+
+        let mut loader = Loader::new("/home/user", &root, BTreeMap::new());
+        loader
+            .load(Path::new("ellipsis.yml"), &Vars::default())
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                Some(When {
+                    expression: "os == \"linux\"".into(),
+                    condition: Condition::Constant(true),
+                }),
+                Some(When {
+                    expression: "(os == \"linux\") && (command_exists(\"apt\"))".into(),
+                    condition: Condition::And(
+                        Box::new(Condition::Constant(true)),
+                        Box::new(Condition::CommandExists("apt".into()))
+                    ),
+                }),
+            ],
+            loader
+                .steps
+                .into_iter()
+                .map(|step| step.when)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn ignore_errors_on_an_include_applies_to_every_included_step() {
+        let root = tempfile::tempdir().unwrap().into_path();
+        write(
+            &root.join("ellipsis.yml"),
+            "todo:\n- include: optional.yml\n  ignore_errors: true\n",
+        );
+        write(
+            &root.join("optional.yml"),
+            "todo:\n- copy:\n    from: a.txt\n    to: ~/out.txt\n",
+        );
+
+        let mut loader = Loader::new("/home/user", &root, BTreeMap::new());
+        loader
+            .load(Path::new("ellipsis.yml"), &Vars::default())
+            .unwrap();
+
+        assert!(loader.steps[0].ignore_errors);
+    }
+
+    #[test]
+    fn names_on_an_include_are_an_error() {
+        let root = tempfile::tempdir().unwrap().into_path();
+        write(
+            &root.join("ellipsis.yml"),
+            "todo:\n- include: optional.yml\n  name: optional\n",
+        );
+        write(&root.join("optional.yml"), "todo: []\n");
+
+        match load(&root) {
+            Err(Error::Todo(_, error)) => {
+                assert!(matches!(*error, Error::IncludeName(name) if name == "optional"))
+            }
+            other => panic!(
+                "expected an include name error, got {:?}",
+                other.map(|_| ())
+            ),
+        }
+    }
+
+    #[test]
+    fn include_cycles_are_an_error() {
+        let root = tempfile::tempdir().unwrap().into_path();
+        write(&root.join("ellipsis.yml"), "include: [other.yml]\n");
+        write(&root.join("other.yml"), "todo:\n- include: ellipsis.yml\n");
+
+        let root = fs::canonicalize(root).unwrap();
+        match load(&root) {
+            Err(Error::Todo(origin, error)) => {
+                assert_eq!(root.join("other.yml"), origin.file);
+                assert!(matches!(
+                    *error,
+                    Error::IncludeCycle(cycle) if cycle == vec![
+                        root.join("ellipsis.yml"),
+                        root.join("other.yml"),
+                        root.join("ellipsis.yml"),
+                    ]
+                ));
+            }
+            other => panic!("expected an include cycle, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn missing_include_names_the_file() {
+        let root = tempfile::tempdir().unwrap().into_path();
+        write(&root.join("ellipsis.yml"), "include: [missing.yml]\n");
+
+        assert!(matches!(
+            load(&root),
+            Err(Error::Read(file, _)) if file == root.join("missing.yml")
+        ));
+    }
+
+    #[test]
+    fn glob_matching_nothing_includes_nothing() {
+        let root = tempfile::tempdir().unwrap().into_path();
+        write(&root.join("ellipsis.yml"), "include: [machines/*.yml]\n");
+
+        assert_eq!(Vec::<Operation>::new(), load(&root).unwrap());
+    }
+
+    #[test]
+    fn invalid_included_yaml_names_the_file() {
+        let root = tempfile::tempdir().unwrap().into_path();
+        write(&root.join("ellipsis.yml"), "include: [broken.yml]\n");
+        write(&root.join("broken.yml"), "todo: [\n");
+
+        assert!(matches!(
+            load(&root),
+            Err(Error::YamlParse(file, _)) if file == root.join("broken.yml")
+        ));
+    }
+}
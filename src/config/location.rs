@@ -0,0 +1,145 @@
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+use crate::domain::Position;
+
+pub fn todo_positions(yaml: &str) -> Vec<Position> {
+    let mut locator = Locator::default();
+
+    if Parser::new(yaml.chars()).load(&mut locator, false).is_err() {
+        return Vec::new();
+    }
+
+    locator.entries
+}
+
+#[derive(Default)]
+struct Locator {
+    stack: Vec<Frame>,
+    entries: Vec<Position>,
+    pending: bool,
+}
+
+enum Frame {
+    Mapping {
+        key: Option<String>,
+        expecting_key: bool,
+    },
+    Sequence,
+}
+
+impl Locator {
+    fn in_todo_list(&self) -> bool {
+        matches!(
+            self.stack.as_slice(),
+            [Frame::Mapping { key: Some(key), .. }, Frame::Sequence] if key == "todo"
+        )
+    }
+
+    fn record(&mut self, marker: Marker) {
+        self.pending = false;
+        self.entries.push(Position {
+            line: marker.line(),
+            column: marker.col() + 1,
+        });
+    }
+
+    fn node(&mut self, scalar: Option<&str>, marker: Marker) {
+        if self.pending {
+            self.record(marker);
+        }
+
+        if self.in_todo_list() {
+            // yaml-rust marks a block collection after its first key, so the
+            // position is taken from the first event inside it instead.
+            if scalar.is_some() {
+                self.record(marker);
+            } else {
+                self.pending = true;
+            }
+        }
+
+        if let Some(Frame::Mapping { key, expecting_key }) = self.stack.last_mut() {
+            if *expecting_key {
+                *key = scalar.map(String::from);
+            }
+        }
+    }
+
+    fn node_finished(&mut self) {
+        if let Some(Frame::Mapping { expecting_key, .. }) = self.stack.last_mut() {
+            *expecting_key = !*expecting_key;
+        }
+    }
+}
+
+impl MarkedEventReceiver for Locator {
+    fn on_event(&mut self, event: Event, marker: Marker) {
+        match event {
+            Event::Scalar(value, ..) => {
+                self.node(Some(&value), marker);
+                self.node_finished();
+            }
+            Event::Alias(_) => {
+                self.node(None, marker);
+                self.node_finished();
+            }
+            Event::MappingStart(_) => {
+                self.node(None, marker);
+                self.stack.push(Frame::Mapping {
+                    key: None,
+                    expecting_key: true,
+                });
+            }
+            Event::SequenceStart(_) => {
+                self.node(None, marker);
+                self.stack.push(Frame::Sequence);
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+                self.node_finished();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::todo_positions;
+    use crate::domain::Position;
+
+    #[test]
+    fn locates_each_todo_entry() {
+        assert_eq!(
+            vec![
+                Position { line: 6, column: 3 },
+                Position {
+                    line: 10,
+                    column: 3
+                },
+            ],
+            todo_positions(indoc! {r#"
+                ---
+                other:
+                  todo:
+                  - not: this
+                todo:
+                - copy:
+                    from: source.txt
+                    to: ~/destination.txt
+                  ignore_errors: true
+                - exec:
+                    command: echo
+                    args: [todo]
+            "#})
+        );
+    }
+
+    #[test]
+    fn invalid_yaml_has_no_positions() {
+        assert!(todo_positions("todo: [").is_empty());
+    }
+}
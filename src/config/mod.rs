@@ -0,0 +1,1409 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::env;
+use std::num::{NonZeroUsize, ParseIntError};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use crate::domain;
+use crate::domain::DriverTypes;
+use crate::facts::Facts;
+use crate::graph;
+
+mod condition;
+mod discovery;
+mod loader;
+mod location;
+mod vars;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct ConfigOuter {
+    #[serde(default)]
+    base_dir: Option<String>,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    vars: BTreeMap<String, String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    todo: Vec<ConfigTodo>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct ConfigTodo {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(flatten)]
+    operation: ConfigOperation,
+    #[serde(default)]
+    ignore_errors: bool,
+    #[serde(default)]
+    when: Option<String>,
+    #[serde(default)]
+    tags: BTreeSet<String>,
+    #[serde(default)]
+    needs: Vec<String>,
+}
+
+impl ConfigTodo {
+    fn into_step(
+        self,
+        home: &str,
+        base_dir: &Path,
+        vars: &vars::Vars,
+        default_name: String,
+        origin: domain::Origin,
+    ) -> Result<domain::Step, Error> {
+        let operation = self
+            .operation
+            .interpolate(vars)
+            .and_then(|operation| operation.into_operation(home, base_dir));
+        let when = self
+            .when
+            .map(|expression| parse_when(expression, home, base_dir, vars))
+            .transpose();
+        let name = self.name.map_or(Ok(default_name), |name| {
+            vars.interpolate(&name).and_then(|name| {
+                if !name.is_empty() && name.bytes().all(|byte| byte.is_ascii_digit()) {
+                    Err(Error::NumberName(name))
+                } else {
+                    Ok(name)
+                }
+            })
+        });
+
+        match (name, operation, when) {
+            (Ok(name), Ok(operation), Ok(when)) => Ok(domain::Step {
+                name,
+                operation,
+                ignore_errors: self.ignore_errors,
+                when,
+                tags: self.tags,
+                needs: self.needs,
+                origin: Some(origin),
+            }),
+            (Err(error), _, _) | (_, Err(error), _) | (_, _, Err(error)) => {
+                Err(Error::Todo(origin, Box::new(error)))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum ConfigOperation {
+    #[serde(rename = "copy")]
+    Copy { to: String, from: String },
+    #[serde(rename = "exec")]
+    Exec(Box<ConfigExec>),
+    #[serde(rename = "include")]
+    Include(String),
+    #[serde(rename = "link")]
+    Link {
+        to: String,
+        from: String,
+        #[serde(default)]
+        overwrite: bool,
+    },
+    #[serde(rename = "shell")]
+    Shell {
+        run: String,
+        #[serde(default)]
+        shell: Option<String>,
+        #[serde(default)]
+        undo: Option<String>,
+    },
+}
+
+impl ConfigOperation {
+    fn interpolate(self, vars: &vars::Vars) -> Result<Self, Error> {
+        Ok(match self {
+            Self::Copy { to, from } => Self::Copy {
+                to: vars.interpolate(&to)?,
+                from: vars.interpolate(&from)?,
+            },
+            Self::Link {
+                to,
+                from,
+                overwrite,
+            } => Self::Link {
+                to: vars.interpolate(&to)?,
+                from: vars.interpolate(&from)?,
+                overwrite,
+            },
+            Self::Exec(exec) => {
+                let exec = *exec;
+
+                Self::Exec(Box::new(ConfigExec {
+                    command: vars.interpolate(&exec.command)?,
+                    args: exec
+                        .args
+                        .iter()
+                        .map(|arg| vars.interpolate(arg))
+                        .collect::<Result<_, _>>()?,
+                    undo: exec.undo.map(|undo| undo.interpolate(vars)).transpose()?,
+                    ..exec
+                }))
+            }
+            Self::Include(_) | Self::Shell { .. } => self,
+        })
+    }
+
+    fn into_operation(self, home: &str, base_dir: &Path) -> Result<domain::Operation, Error> {
+        match self {
+            Self::Copy { to, from } => Ok(domain::Operation::new_copy(home, base_dir, &to, &from)),
+            Self::Link {
+                to,
+                from,
+                overwrite,
+            } => Ok(domain::Operation::new_link(
+                home, base_dir, &to, &from, overwrite,
+            )),
+            Self::Exec(exec) => exec.into_operation(home, base_dir),
+            Self::Include(_) => unreachable!("includes are expanded by the loader"),
+            Self::Shell { run, shell, undo } => Ok(domain::Operation::new_shell(
+                base_dir,
+                run,
+                shell.unwrap_or_else(domain::default_shell),
+                undo,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct ConfigExec {
+    command: String,
+    args: Vec<String>,
+    #[serde(default = "default_allowed_exit_codes")]
+    allowed_exit_codes: Vec<i32>,
+    #[serde(default)]
+    output: ConfigOutput,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    #[serde(default)]
+    clear_env: bool,
+    #[serde(default)]
+    cwd: Option<String>,
+    #[serde(default)]
+    stdin: Option<ConfigStdin>,
+    #[serde(default)]
+    creates: Option<String>,
+    #[serde(default)]
+    removes: Option<String>,
+    #[serde(default)]
+    unless: Option<String>,
+    #[serde(default)]
+    onlyif: Option<String>,
+    #[serde(default)]
+    timeout: Option<String>,
+    #[serde(default)]
+    retries: u32,
+    #[serde(default)]
+    retry_delay: Option<String>,
+    #[serde(default)]
+    undo: Option<ConfigUndo>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct ConfigUndo {
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+impl ConfigUndo {
+    fn interpolate(self, vars: &vars::Vars) -> Result<Self, Error> {
+        Ok(Self {
+            command: vars.interpolate(&self.command)?,
+            args: self
+                .args
+                .iter()
+                .map(|arg| vars.interpolate(arg))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl ConfigExec {
+    fn into_operation(self, home: &str, base_dir: &Path) -> Result<domain::Operation, Error> {
+        let path = |location: &str| {
+            domain::OperationPath::new(base_dir, Path::new(home), location).location
+        };
+
+        Ok(domain::Operation::Exec(Box::new(domain::Exec {
+            working_dir: self
+                .cwd
+                .as_deref()
+                .map_or_else(|| base_dir.to_path_buf(), path),
+            command: self.command,
+            args: self.args,
+            allowed_exit_codes: self.allowed_exit_codes,
+            output: self.output.into(),
+            env: self.env,
+            clear_env: self.clear_env,
+            stdin: self.stdin.map(|stdin| match stdin {
+                ConfigStdin::Literal(input) => domain::Stdin::Literal(input),
+                ConfigStdin::File { file } => domain::Stdin::File(path(&file)),
+            }),
+            guards: domain::Guards {
+                creates: self.creates.as_deref().map(path),
+                removes: self.removes.as_deref().map(path),
+                unless: self.unless,
+                onlyif: self.onlyif,
+                shell: domain::default_shell(),
+            },
+            timeout: self.timeout.as_deref().map(parse_duration).transpose()?,
+            retries: self.retries,
+            retry_delay: self
+                .retry_delay
+                .as_deref()
+                .map(parse_duration)
+                .transpose()?
+                .unwrap_or(domain::DEFAULT_RETRY_DELAY),
+            undo: self.undo.map(|undo| domain::Undo {
+                command: undo.command,
+                args: undo.args,
+            }),
+        })))
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum ConfigStdin {
+    Literal(String),
+    File { file: String },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+enum ConfigOutput {
+    #[default]
+    #[serde(rename = "stream")]
+    Stream,
+    #[serde(rename = "capture")]
+    Capture,
+}
+
+impl From<ConfigOutput> for domain::Output {
+    fn from(output: ConfigOutput) -> Self {
+        match output {
+            ConfigOutput::Stream => Self::Stream,
+            ConfigOutput::Capture => Self::Capture,
+        }
+    }
+}
+
+fn parse_when(
+    expression: String,
+    home: &str,
+    base_dir: &Path,
+    vars: &vars::Vars,
+) -> Result<domain::When, Error> {
+    let condition = condition::parse(&expression, vars, |path| {
+        domain::OperationPath::new(base_dir, Path::new(home), path).location
+    })?;
+
+    Ok(domain::When {
+        expression,
+        condition,
+    })
+}
+
+fn parse_duration(duration: &str) -> Result<Duration, Error> {
+    humantime::parse_duration(duration).map_err(|error| Error::Duration(duration.into(), error))
+}
+
+fn default_allowed_exit_codes() -> Vec<i32> {
+    vec![0]
+}
+
+pub struct Config {
+    pub driver: DriverTypes,
+    pub keep_going: bool,
+    pub jobs: usize,
+    pub selection: domain::Selection,
+    pub steps: Vec<domain::Step>,
+}
+
+impl TryFrom<&ArgMatches> for Config {
+    type Error = Error;
+
+    fn try_from(matches: &ArgMatches) -> Result<Self, Self::Error> {
+        Self::with_facts(matches, &Facts::gather())
+    }
+}
+
+impl Config {
+    pub fn with_facts(matches: &ArgMatches, facts: &Facts) -> Result<Self, Error> {
+        let config_location = location(matches)?;
+        let home = matches.value_of("home").unwrap();
+        let dry_run = matches.is_present("dry-run");
+        let jobs = parse_jobs(matches.value_of("jobs").unwrap())?;
+        let current_dir = env::current_dir().map_err(Error::CurrentDir)?;
+        let overrides = matches
+            .values_of("set")
+            .into_iter()
+            .flatten()
+            .map(|set| {
+                set.split_once('=')
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .ok_or_else(|| Error::InvalidSet(set.into()))
+            })
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+        let vars = vars::Vars::new(facts.to_vars(), env::vars().collect());
+
+        let mut loader = loader::Loader::new(home, &current_dir, overrides);
+        loader.load(&config_location, &vars)?;
+
+        let mut names = BTreeSet::new();
+        for step in &loader.steps {
+            if !names.insert(step.name.clone()) {
+                return Err(Error::DuplicateName(
+                    step.name.clone(),
+                    step.origin.clone().map(Box::new),
+                ));
+            }
+        }
+
+        let steps = graph::sort(loader.steps)?;
+
+        let only = values(matches, "only");
+        let start_at = matches.value_of("start-at").map(String::from);
+        if let Some(unknown) = only
+            .iter()
+            .chain(start_at.iter())
+            .find(|name| !names.contains(*name))
+        {
+            return Err(Error::UnknownStep(unknown.clone()));
+        }
+
+        let mut tags = values(matches, "tags");
+        for profile in values(matches, "profile") {
+            match loader.profiles.get(&profile) {
+                Some(profile_tags) => tags.extend(profile_tags.iter().cloned()),
+                None => {
+                    return Err(Error::UnknownProfile(
+                        profile,
+                        loader
+                            .profiles
+                            .keys()
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ))
+                }
+            }
+        }
+
+        Ok(Self {
+            driver: if dry_run {
+                DriverTypes::Blackhole
+            } else {
+                DriverTypes::Io
+            },
+            keep_going: matches.is_present("keep-going"),
+            // A dry run does nothing worth waiting for, and running it one
+            // operation at a time keeps its log in the order of the todo list
+            jobs: if dry_run { 1 } else { jobs },
+            selection: domain::Selection {
+                tags,
+                skip_tags: values(matches, "skip-tags"),
+                only,
+                start_at,
+            },
+            steps,
+        })
+    }
+}
+
+fn parse_jobs(jobs: &str) -> Result<usize, Error> {
+    jobs.parse::<NonZeroUsize>()
+        .map(NonZeroUsize::get)
+        .map_err(|error| Error::Jobs(jobs.into(), error))
+}
+
+fn values(matches: &ArgMatches, name: &str) -> BTreeSet<String> {
+    matches
+        .values_of(name)
+        .into_iter()
+        .flatten()
+        .map(String::from)
+        .collect()
+}
+
+fn display_cycle(files: &[PathBuf]) -> String {
+    files
+        .iter()
+        .map(|file| format!("`{}`", file.display()))
+        .collect::<Vec<_>>()
+        .join(" includes ")
+}
+
+fn display_variable_cycle(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| format!("`{}`", name))
+        .collect::<Vec<_>>()
+        .join(" uses ")
+}
+
+pub fn location(matches: &ArgMatches) -> Result<PathBuf, Error> {
+    if let Some(config) = matches.value_of("config") {
+        return Ok(PathBuf::from(config));
+    }
+
+    let current_dir = env::current_dir().map_err(Error::CurrentDir)?;
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|config_home| !config_home.is_empty())
+        .map_or_else(
+            || Path::new(matches.value_of("home").unwrap()).join(".config"),
+            PathBuf::from,
+        );
+
+    discovery::find(&current_dir, &config_home)
+        .ok_or_else(|| Error::NotFound(current_dir, config_home.join("ellipsis")))
+}
+
+#[derive(ThisError, Debug)]
+pub enum Error {
+    #[error(
+        "no ellipsis.yml or .ellipsis.yml found in `{0}` or its parents, nor ellipsis.yml in `{1}`"
+    )]
+    NotFound(PathBuf, PathBuf),
+    #[error("could not read config file `{0}`")]
+    Read(PathBuf, #[source] std::io::Error),
+    #[error("could not parse config file `{0}`")]
+    YamlParse(PathBuf, #[source] serde_yaml::Error),
+    #[error("could not find the current directory")]
+    CurrentDir(#[source] std::io::Error),
+    #[error("{0} is invalid")]
+    Todo(domain::Origin, #[source] Box<Error>),
+    #[error("invalid include pattern `{0}`")]
+    IncludePattern(String, #[source] glob::PatternError),
+    #[error("an include can't be named `{0}`, name the entries in the included files instead")]
+    IncludeName(String),
+    #[error(
+        "an entry can't be named `{0}`, names that are only digits are kept for unnamed entries"
+    )]
+    NumberName(String),
+    #[error("include cycle: {}", display_cycle(.0))]
+    IncludeCycle(Vec<PathBuf>),
+    #[error("invalid vars in `{0}`")]
+    Vars(PathBuf, #[source] Box<Error>),
+    #[error("undefined variable `{0}`")]
+    UndefinedVariable(String),
+    #[error("variables use each other in a cycle: {}", display_variable_cycle(.0))]
+    VariableCycle(Vec<String>),
+    #[error("unterminated `${{` in `{0}`")]
+    UnterminatedVariable(String),
+    #[error("more than one operation is named `{0}`{}", .1.as_ref().map(|origin| format!(", the second is {}", origin)).unwrap_or_default())]
+    DuplicateName(String, Option<Box<domain::Origin>>),
+    #[error(transparent)]
+    Graph(#[from] graph::Error),
+    #[error("there is no operation named `{0}`, see `ellipsis list` for their names")]
+    UnknownStep(String),
+    #[error("unknown profile `{0}`, the profiles are: {1}")]
+    UnknownProfile(String, String),
+    #[error("invalid condition `{0}`: {1}")]
+    Condition(String, String),
+    #[error("`--set {0}` should be in the form `name=value`")]
+    InvalidSet(String),
+    #[error("invalid duration `{0}`")]
+    Duration(String, #[source] humantime::DurationError),
+    #[error("`--jobs {0}` should be a number of 1 or more")]
+    Jobs(String, #[source] ParseIntError),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::convert::TryFrom;
+    use std::fs;
+    use std::io::Write;
+    use std::path::Path;
+    use std::time::Duration;
+
+    use indoc::indoc;
+    use tempfile::TempDir;
+
+    use super::{Config, Error};
+    use crate::cli::cli;
+    use crate::domain;
+    use crate::domain::{Operation, Position};
+    use crate::facts::Facts;
+
+    #[test]
+    fn no_config_defined() {
+        let args = cli().get_matches_from(vec!["ellipsis", "--config", "/does/not/exist.yml"]);
+        assert!(matches!(
+            Config::try_from(&args),
+            Err(Error::Read(path, _)) if path == Path::new("/does/not/exist.yml")
+        ));
+    }
+
+    #[test]
+    fn invalid_yaml_names_the_file() {
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        write!(tmpfile, "todo:\n- teleport: {{}}\n").unwrap();
+
+        let args = cli().get_matches_from(vec![
+            "ellipsis",
+            "--config",
+            &tmpfile.path().display().to_string(),
+        ]);
+
+        assert!(matches!(
+            Config::try_from(&args),
+            Err(Error::YamlParse(path, _)) if path == tmpfile.path()
+        ));
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_config_file() {
+        let home = tempfile::tempdir().unwrap();
+        let dotfiles = home.path().join("dotfiles");
+        let config = parse_yaml_at(
+            indoc! {r#"
+            ---
+            todo:
+            - link:
+                from: vimrc
+                to: ~/.vimrc
+            - shell:
+                run: make
+                shell: /bin/sh
+        "#},
+            &home,
+            &dotfiles.join("ellipsis.yml"),
+        );
+
+        assert_eq!(
+            vec![
+                Operation::new_link(
+                    &home.path().display().to_string(),
+                    &dotfiles,
+                    "~/.vimrc",
+                    "vimrc",
+                    false
+                ),
+                Operation::new_shell(&dotfiles, "make".into(), "/bin/sh".into(), None),
+            ],
+            config
+                .steps
+                .into_iter()
+                .map(|step| step.operation)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn base_dir_overrides_the_config_directory() {
+        let home = tempfile::tempdir().unwrap();
+        let config = parse_yaml_at(
+            indoc! {r#"
+            ---
+            base_dir: ../files
+            todo:
+            - copy:
+                from: gitconfig
+                to: ~/.gitconfig
+        "#},
+            &home,
+            &home.path().join("config").join("ellipsis.yml"),
+        );
+
+        assert_eq!(
+            Operation::new_copy(
+                &home.path().display().to_string(),
+                &home.path().join("config").join("../files"),
+                "~/.gitconfig",
+                "gitconfig",
+            ),
+            config.steps[0].operation
+        );
+    }
+
+    #[test]
+    fn base_dir_in_home() {
+        let home = tempfile::tempdir().unwrap();
+        let config = parse_yaml(
+            indoc! {r#"
+            ---
+            base_dir: ~/dotfiles
+            todo:
+            - exec:
+                command: make
+                args: []
+        "#},
+            &home,
+        );
+
+        assert_eq!(
+            Operation::Exec(Box::new(domain::Exec::new(
+                &home.path().join("dotfiles"),
+                "make".into(),
+                vec![],
+            ))),
+            config.steps[0].operation
+        );
+    }
+
+    #[test]
+    fn vars_are_interpolated_and_set_overrides_them() {
+        let home = tempfile::tempdir().unwrap();
+        let config_location = home.path().join("ellipsis.yml");
+        fs::write(
+            &config_location,
+            indoc! {r#"
+            ---
+            vars:
+              machine: laptop
+              greeting: hello
+            todo:
+            - copy:
+                from: ${machine}/gitconfig
+                to: ~/.gitconfig
+            - exec:
+                command: echo
+                args: [ "${greeting}", "${env.ELLIPSIS_TEST_UNSET:-world}" ]
+        "#},
+        )
+        .unwrap();
+
+        let args = cli().get_matches_from(vec![
+            "ellipsis",
+            "--home",
+            &home.path().display().to_string(),
+            "--config",
+            &config_location.display().to_string(),
+            "--set",
+            "machine=desktop",
+        ]);
+
+        assert_eq!(
+            vec![
+                Operation::new_copy(
+                    &home.path().display().to_string(),
+                    home.path(),
+                    "~/.gitconfig",
+                    "desktop/gitconfig",
+                ),
+                Operation::Exec(Box::new(domain::Exec::new(
+                    home.path(),
+                    "echo".into(),
+                    vec!["hello".into(), "world".into()],
+                ))),
+            ],
+            Config::try_from(&args)
+                .unwrap()
+                .steps
+                .into_iter()
+                .map(|step| step.operation)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn facts_are_vars() {
+        let home = tempfile::tempdir().unwrap();
+        let config_location = home.path().join("ellipsis.yml");
+        fs::write(
+            &config_location,
+            "todo:\n- copy:\n    from: ${facts.hostname}/${facts.distro:-generic}.conf\n    to: ~/out.conf\n",
+        )
+        .unwrap();
+
+        let args = cli().get_matches_from(vec![
+            "ellipsis",
+            "--home",
+            &home.path().display().to_string(),
+            "--config",
+            &config_location.display().to_string(),
+        ]);
+        let facts = Facts {
+            hostname: "laptop".into(),
+            ..Facts::default()
+        };
+
+        assert_eq!(
+            Operation::new_copy(
+                &home.path().display().to_string(),
+                home.path(),
+                "~/out.conf",
+                "laptop/generic.conf",
+            ),
+            Config::with_facts(&args, &facts).unwrap().steps[0].operation
+        );
+    }
+
+    #[test]
+    fn undefined_variables_are_an_error() {
+        let home = tempfile::tempdir().unwrap();
+        let config_location = home.path().join("ellipsis.yml");
+        fs::write(
+            &config_location,
+            "todo:\n- copy:\n    from: ${missing}\n    to: ~/out.txt\n",
+        )
+        .unwrap();
+
+        let args = cli().get_matches_from(vec![
+            "ellipsis",
+            "--home",
+            &home.path().display().to_string(),
+            "--config",
+            &config_location.display().to_string(),
+        ]);
+
+        assert!(matches!(
+            Config::try_from(&args),
+            Err(Error::Todo(_, error))
+                if matches!(&*error, Error::UndefinedVariable(name) if name == "missing")
+        ));
+    }
+
+    #[test]
+    fn steps_record_their_origin() {
+        let home = tempfile::tempdir().unwrap();
+        let config = parse_yaml(
+            indoc! {r#"
+            ---
+            todo:
+            - copy:
+                from: source.txt
+                to: ~/destination.txt
+            - link:
+                from: source.txt
+                to: ~/destination.txt
+        "#},
+            &home,
+        );
+
+        let positions: Vec<_> = config
+            .steps
+            .into_iter()
+            .map(|step| {
+                let origin = step.origin.unwrap();
+                (origin.index, origin.position)
+            })
+            .collect();
+
+        assert_eq!(
+            vec![
+                (1, Some(Position { line: 3, column: 3 })),
+                (2, Some(Position { line: 6, column: 3 })),
+            ],
+            positions
+        );
+    }
+
+    #[test]
+    fn exec_operation() {
+        let home = tempfile::tempdir().unwrap();
+        assert_yaml_parsing(
+            indoc! {r#"
+            ---
+            todo:
+            - exec:
+                command: echo
+                args: [hello]
+        "#},
+            &[domain::Operation::Exec(Box::new(domain::Exec::new(
+                home.path(),
+                "echo".into(),
+                vec!["hello".into()],
+            )))],
+            &home,
+        );
+    }
+
+    #[test]
+    fn exec_operation_with_allowed_exit_codes() {
+        let home = tempfile::tempdir().unwrap();
+        assert_yaml_parsing(
+            indoc! {r#"
+            ---
+            todo:
+            - exec:
+                command: grep
+                args: [needle, haystack.txt]
+                allowed_exit_codes: [0, 1]
+        "#},
+            &[domain::Operation::Exec(Box::new(domain::Exec {
+                allowed_exit_codes: vec![0, 1],
+                ..domain::Exec::new(
+                    home.path(),
+                    "grep".into(),
+                    vec!["needle".into(), "haystack.txt".into()],
+                )
+            }))],
+            &home,
+        );
+    }
+
+    #[test]
+    fn ignore_errors() {
+        let home = tempfile::tempdir().unwrap();
+        let config = parse_yaml(
+            indoc! {r#"
+            ---
+            todo:
+            - link:
+                from: source.txt
+                to: ~/destination.txt
+              ignore_errors: true
+            - exec:
+                command: "false"
+                args: []
+              ignore_errors: true
+            - copy:
+                from: source.txt
+                to: ~/destination.txt
+        "#},
+            &home,
+        );
+
+        assert_eq!(
+            vec![true, true, false],
+            config
+                .steps
+                .iter()
+                .map(|step| step.ignore_errors)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn keep_going() {
+        let home = tempfile::tempdir().unwrap();
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        write!(tmpfile, "---\ntodo: []\n").unwrap();
+
+        let args = cli().get_matches_from(vec![
+            "ellipsis",
+            "--home",
+            &home.path().display().to_string(),
+            "--config",
+            &tmpfile.path().display().to_string(),
+            "--keep-going",
+        ]);
+
+        assert!(Config::try_from(&args).unwrap().keep_going);
+    }
+
+    fn config_with_jobs(extra_args: &[&str]) -> Result<Config, Error> {
+        let home = tempfile::tempdir().unwrap();
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        write!(tmpfile, "---\ntodo: []\n").unwrap();
+
+        let home = home.path().display().to_string();
+        let config = tmpfile.path().display().to_string();
+        let mut args = vec!["ellipsis", "--home", &home, "--config", &config];
+        args.extend(extra_args);
+
+        Config::try_from(&cli().get_matches_from(args))
+    }
+
+    #[test]
+    fn jobs() {
+        assert_eq!(1, config_with_jobs(&[]).unwrap().jobs);
+        assert_eq!(4, config_with_jobs(&["--jobs", "4"]).unwrap().jobs);
+        assert_eq!(1, config_with_jobs(&["-j", "4", "--dry-run"]).unwrap().jobs);
+    }
+
+    #[test]
+    fn jobs_must_be_at_least_one() {
+        assert!(matches!(
+            config_with_jobs(&["--jobs", "0"]),
+            Err(Error::Jobs(jobs, _)) if jobs == "0"
+        ));
+        assert!(matches!(
+            config_with_jobs(&["--jobs", "many"]),
+            Err(Error::Jobs(jobs, _)) if jobs == "many"
+        ));
+    }
+
+    fn config_with_profiles(extra_args: &[&str]) -> Result<Config, Error> {
+        let home = tempfile::tempdir().unwrap();
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            tmpfile,
+            "{}",
+            indoc! {r#"
+            ---
+            profiles:
+              desktop: [gui, shell]
+              server: [shell]
+            todo:
+            - shell:
+                run: "true"
+              tags: [gui]
+        "#}
+        )
+        .unwrap();
+
+        let mut args = vec![
+            "ellipsis".to_string(),
+            "--home".into(),
+            home.path().display().to_string(),
+            "--config".into(),
+            tmpfile.path().display().to_string(),
+        ];
+        args.extend(extra_args.iter().map(|arg| arg.to_string()));
+
+        Config::try_from(&cli().get_matches_from(args))
+    }
+
+    #[test]
+    fn tags_and_profiles_select_operations() {
+        let config = config_with_profiles(&[
+            "--tags",
+            "git,work",
+            "--profile",
+            "server",
+            "--skip-tags",
+            "gui",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            domain::Selection {
+                tags: BTreeSet::from(["git".into(), "shell".into(), "work".into()]),
+                skip_tags: BTreeSet::from(["gui".into()]),
+                ..domain::Selection::default()
+            },
+            config.selection
+        );
+        assert_eq!(BTreeSet::from(["gui".into()]), config.steps[0].tags);
+    }
+
+    #[test]
+    fn names_are_given_or_generated() {
+        let home = tempfile::tempdir().unwrap();
+        let config = parse_yaml(
+            indoc! {r#"
+            ---
+            vars:
+              machine: laptop
+            todo:
+            - copy:
+                from: source.txt
+                to: ~/destination.txt
+              name: ${machine}-copy
+            - link:
+                from: source.txt
+                to: ~/destination.txt
+        "#},
+            &home,
+        );
+
+        assert_eq!(
+            vec!["laptop-copy", "2"],
+            config
+                .steps
+                .iter()
+                .map(|step| step.name.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn names_must_be_unique() {
+        let home = tempfile::tempdir().unwrap();
+        let config_location = home.path().join("ellipsis.yml");
+        fs::write(
+            &config_location,
+            "todo:\n- shell: { run: a }\n  name: b\n- shell: { run: b }\n  name: b\n",
+        )
+        .unwrap();
+
+        let args = cli().get_matches_from(vec![
+            "ellipsis",
+            "--config",
+            &config_location.display().to_string(),
+        ]);
+
+        assert!(matches!(
+            Config::try_from(&args),
+            Err(Error::DuplicateName(name, Some(origin))) if name == "b" && origin.index == 2
+        ));
+    }
+
+    #[test]
+    fn names_that_are_only_digits_are_kept_for_unnamed_entries() {
+        let home = tempfile::tempdir().unwrap();
+        let config_location = home.path().join("ellipsis.yml");
+        fs::write(
+            &config_location,
+            "todo:\n- shell: { run: a }\n  name: \"2\"\n- shell: { run: b }\n",
+        )
+        .unwrap();
+
+        let args = cli().get_matches_from(vec![
+            "ellipsis",
+            "--config",
+            &config_location.display().to_string(),
+        ]);
+
+        assert!(matches!(
+            Config::try_from(&args),
+            Err(Error::Todo(origin, error))
+                if origin.index == 1 && matches!(&*error, Error::NumberName(name) if name == "2")
+        ));
+    }
+
+    #[test]
+    fn only_and_start_at_must_name_an_operation() {
+        let config = config_with_profiles(&["--only", "1", "--start-at", "1"]).unwrap();
+        assert_eq!(Some("1".into()), config.selection.start_at);
+
+        assert!(matches!(
+            config_with_profiles(&["--only", "1,missing"]),
+            Err(Error::UnknownStep(name)) if name == "missing"
+        ));
+        assert!(matches!(
+            config_with_profiles(&["--start-at", "missing"]),
+            Err(Error::UnknownStep(name)) if name == "missing"
+        ));
+    }
+
+    #[test]
+    fn unknown_profile() {
+        assert!(matches!(
+            config_with_profiles(&["--profile", "laptop"]),
+            Err(Error::UnknownProfile(profile, profiles))
+                if profile == "laptop" && profiles == "desktop, server"
+        ));
+    }
+
+    #[test]
+    fn exec_operation_capturing_output() {
+        let home = tempfile::tempdir().unwrap();
+        assert_yaml_parsing(
+            indoc! {r#"
+            ---
+            todo:
+            - exec:
+                command: echo
+                args: [hello]
+                output: capture
+        "#},
+            &[domain::Operation::Exec(Box::new(domain::Exec {
+                output: domain::Output::Capture,
+                ..domain::Exec::new(home.path(), "echo".into(), vec!["hello".into()])
+            }))],
+            &home,
+        );
+    }
+
+    #[test]
+    fn exec_operation_with_environment_directory_and_stdin() {
+        let home = tempfile::tempdir().unwrap();
+        assert_yaml_parsing(
+            indoc! {r#"
+            ---
+            todo:
+            - exec:
+                command: git
+                args: [status]
+                env:
+                  GIT_DIR: ~/.dotfiles.git
+                clear_env: true
+                cwd: ~/
+                stdin: "yes"
+            - exec:
+                command: crontab
+                args: ["-"]
+                stdin:
+                  file: ~/crontab
+        "#},
+            &[
+                domain::Operation::Exec(Box::new(domain::Exec {
+                    env: vec![("GIT_DIR".into(), "~/.dotfiles.git".into())]
+                        .into_iter()
+                        .collect(),
+                    clear_env: true,
+                    stdin: Some(domain::Stdin::Literal("yes".into())),
+                    ..domain::Exec::new(home.path(), "git".into(), vec!["status".into()])
+                })),
+                domain::Operation::Exec(Box::new(domain::Exec {
+                    stdin: Some(domain::Stdin::File(home.path().join("crontab"))),
+                    ..domain::Exec::new(home.path(), "crontab".into(), vec!["-".into()])
+                })),
+            ],
+            &home,
+        );
+    }
+
+    #[test]
+    fn exec_operation_with_guards() {
+        let home = tempfile::tempdir().unwrap();
+        assert_yaml_parsing(
+            indoc! {r#"
+            ---
+            todo:
+            - exec:
+                command: git
+                args: [clone, https://example.com/plugins.git, ~/.plugins]
+                creates: ~/.plugins
+                removes: ~/.plugins.lock
+                unless: test -d ~/.plugins
+                onlyif: command -v git
+        "#},
+            &[domain::Operation::Exec(Box::new(domain::Exec {
+                guards: domain::Guards {
+                    creates: Some(home.path().join(".plugins")),
+                    removes: Some(home.path().join(".plugins.lock")),
+                    unless: Some("test -d ~/.plugins".into()),
+                    onlyif: Some("command -v git".into()),
+                    ..domain::Guards::default()
+                },
+                ..domain::Exec::new(
+                    home.path(),
+                    "git".into(),
+                    vec![
+                        "clone".into(),
+                        "https://example.com/plugins.git".into(),
+                        "~/.plugins".into(),
+                    ],
+                )
+            }))],
+            &home,
+        );
+    }
+
+    #[test]
+    fn exec_operation_with_timeout_and_retries() {
+        let home = tempfile::tempdir().unwrap();
+        assert_yaml_parsing(
+            indoc! {r#"
+            ---
+            todo:
+            - exec:
+                command: curl
+                args: [https://example.com]
+                timeout: 30s
+                retries: 3
+                retry_delay: 1m 30s
+        "#},
+            &[domain::Operation::Exec(Box::new(domain::Exec {
+                timeout: Some(Duration::from_secs(30)),
+                retries: 3,
+                retry_delay: Duration::from_secs(90),
+                ..domain::Exec::new(
+                    home.path(),
+                    "curl".into(),
+                    vec!["https://example.com".into()],
+                )
+            }))],
+            &home,
+        );
+    }
+
+    #[test]
+    fn exec_operation_with_undo() {
+        let home = tempfile::tempdir().unwrap();
+        assert_yaml_parsing(
+            indoc! {r#"
+            ---
+            vars:
+              package: neovim
+            todo:
+            - exec:
+                command: brew
+                args: [install, "${package}"]
+                undo:
+                  command: brew
+                  args: [uninstall, "${package}"]
+        "#},
+            &[domain::Operation::Exec(Box::new(domain::Exec {
+                undo: Some(domain::Undo {
+                    command: "brew".into(),
+                    args: vec!["uninstall".into(), "neovim".into()],
+                }),
+                ..domain::Exec::new(
+                    home.path(),
+                    "brew".into(),
+                    vec!["install".into(), "neovim".into()],
+                )
+            }))],
+            &home,
+        );
+    }
+
+    #[test]
+    fn exec_operation_with_invalid_timeout() {
+        let home = tempfile::tempdir().unwrap();
+        let mut tmpfile = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            tmpfile,
+            "{}",
+            indoc! {r#"
+            ---
+            todo:
+            - exec:
+                command: curl
+                args: [https://example.com]
+                timeout: soon
+        "#}
+        )
+        .unwrap();
+
+        let args = cli().get_matches_from(vec![
+            "ellipsis",
+            "--home",
+            &home.path().display().to_string(),
+            "--config",
+            &tmpfile.path().display().to_string(),
+        ]);
+
+        assert!(matches!(
+            Config::try_from(&args),
+            Err(Error::Todo(origin, error))
+                if origin.index == 1
+                    && origin.position == Some(Position { line: 3, column: 3 })
+                    && matches!(&*error, Error::Duration(duration, _) if duration == "soon")
+        ));
+    }
+
+    #[test]
+    fn shell_operation() {
+        let home = tempfile::tempdir().unwrap();
+        assert_yaml_parsing(
+            indoc! {r#"
+            ---
+            todo:
+            - shell:
+                run: |
+                  cd ~/.vim
+                  git pull
+                shell: bash
+        "#},
+            &[domain::Operation::Shell {
+                working_dir: home.path().to_path_buf(),
+                command: domain::OperationCommand {
+                    run: "cd ~/.vim\ngit pull\n".into(),
+                    shell: "bash".into(),
+                    undo: None,
+                },
+            }],
+            &home,
+        );
+    }
+
+    #[test]
+    fn shell_operation_with_undo() {
+        let home = tempfile::tempdir().unwrap();
+        assert_yaml_parsing(
+            indoc! {r#"
+            ---
+            todo:
+            - shell:
+                run: git clone https://example.com/vim.git ~/.vim
+                shell: bash
+                undo: rm -rf ~/.vim
+        "#},
+            &[domain::Operation::new_shell(
+                home.path(),
+                "git clone https://example.com/vim.git ~/.vim".into(),
+                "bash".into(),
+                Some("rm -rf ~/.vim".into()),
+            )],
+            &home,
+        );
+    }
+
+    #[test]
+    fn copy_operation() {
+        let home = tempfile::tempdir().unwrap();
+        assert_yaml_parsing(
+            indoc! {r#"
+            ---
+            todo:
+            - copy:
+                from: source.txt
+                to: ~/destination.txt
+        "#},
+            &[domain::Operation::Copy {
+                from: domain::OperationPath::new(home.path(), home.path(), "source.txt"),
+                to: domain::OperationPath::new(home.path(), home.path(), "~/destination.txt"),
+            }],
+            &home,
+        );
+    }
+
+    #[test]
+    fn link_operation() {
+        let home = tempfile::tempdir().unwrap();
+
+        assert_yaml_parsing(
+            indoc! {r#"
+            ---
+            todo:
+            - link:
+                from: source.txt
+                to: ~/destination.txt
+        "#},
+            &[domain::Operation::Link {
+                from: domain::OperationPath::new(home.path(), home.path(), "source.txt"),
+                to: domain::OperationPath::new(home.path(), home.path(), "~/destination.txt"),
+                overwrite: false,
+            }],
+            &home,
+        );
+    }
+
+    #[test]
+    fn link_and_overwrite() {
+        let home = tempfile::tempdir().unwrap();
+
+        assert_yaml_parsing(
+            indoc! {r#"
+            ---
+            todo:
+            - link:
+                from: source.txt
+                to: ~/destination.txt
+                overwrite: true
+        "#},
+            &[domain::Operation::Link {
+                from: domain::OperationPath::new(home.path(), home.path(), "source.txt"),
+                to: domain::OperationPath::new(home.path(), home.path(), "~/destination.txt"),
+                overwrite: true,
+            }],
+            &home,
+        );
+    }
+
+    fn assert_yaml_parsing(yaml: &str, expected: &[Operation], home: &TempDir) {
+        let operations: Vec<Operation> = parse_yaml(yaml, home)
+            .steps
+            .into_iter()
+            .map(|step| step.operation)
+            .collect();
+        assert_eq!(operations, expected);
+    }
+
+    fn parse_yaml(yaml: &str, home: &TempDir) -> Config {
+        parse_yaml_at(yaml, home, &home.path().join("ellipsis.yml"))
+    }
+
+    fn parse_yaml_at(yaml: &str, home: &TempDir, config_location: &Path) -> Config {
+        fs::create_dir_all(config_location.parent().unwrap()).unwrap();
+        fs::write(config_location, yaml).unwrap();
+
+        let args = cli().get_matches_from(vec![
+            "ellipsis",
+            "--home",
+            &home.path().display().to_string(),
+            "--config",
+            &config_location.display().to_string(),
+        ]);
+
+        Config::try_from(&args).unwrap()
+    }
+}
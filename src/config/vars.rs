@@ -0,0 +1,224 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::config::Error;
+
+pub(super) const ENV_PREFIX: &str = "env.";
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Vars {
+    values: BTreeMap<String, String>,
+    env: BTreeMap<String, String>,
+}
+
+impl Vars {
+    pub const fn new(values: BTreeMap<String, String>, env: BTreeMap<String, String>) -> Self {
+        Self { values, env }
+    }
+
+    /// These vars with more of their own, which can use each other as well
+    /// as the vars they are added to
+    pub fn with(&self, values: BTreeMap<String, String>) -> Result<Self, Error> {
+        let mut scoped = self.clone();
+        let mut resolved = BTreeSet::new();
+
+        for name in values.keys() {
+            scoped.resolve(name, &values, &mut resolved, &mut Vec::new())?;
+        }
+
+        Ok(scoped)
+    }
+
+    /// Work out a new var, after any new vars it uses
+    fn resolve(
+        &mut self,
+        name: &str,
+        values: &BTreeMap<String, String>,
+        resolved: &mut BTreeSet<String>,
+        resolving: &mut Vec<String>,
+    ) -> Result<(), Error> {
+        if resolved.contains(name) {
+            return Ok(());
+        }
+
+        resolving.push(name.into());
+        if resolving[..resolving.len() - 1]
+            .iter()
+            .any(|other| other == name)
+        {
+            return Err(Error::VariableCycle(resolving.clone()));
+        }
+
+        let value = &values[name];
+        let mut uses = Vec::new();
+        Self::substitute(value, |expression| {
+            uses.push(Self::split_default(expression).0.to_string());
+            Ok(String::new())
+        })?;
+
+        for used in uses.iter().filter(|used| values.contains_key(*used)) {
+            self.resolve(used, values, resolved, resolving)?;
+        }
+
+        let value = self.interpolate(value)?;
+        self.values.insert(name.into(), value);
+        resolved.insert(name.into());
+        resolving.pop();
+
+        Ok(())
+    }
+
+    pub fn overridden_by(mut self, overrides: &BTreeMap<String, String>) -> Self {
+        self.values.extend(
+            overrides
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+        self
+    }
+
+    pub fn interpolate(&self, input: &str) -> Result<String, Error> {
+        Self::substitute(input, |expression| self.evaluate(expression))
+    }
+
+    fn substitute<F>(input: &str, mut evaluate: F) -> Result<String, Error>
+    where
+        F: FnMut(&str) -> Result<String, Error>,
+    {
+        let mut output = String::with_capacity(input.len());
+        let mut rest = input;
+
+        while let Some(start) = rest.find('$') {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            if let Some(escaped) = rest.strip_prefix("$${") {
+                output.push_str("${");
+                rest = escaped;
+            } else if let Some(expression) = rest.strip_prefix("${") {
+                let end = expression
+                    .find('}')
+                    .ok_or_else(|| Error::UnterminatedVariable(input.into()))?;
+                output.push_str(&evaluate(&expression[..end])?);
+                rest = &expression[end + 1..];
+            } else {
+                output.push('$');
+                rest = &rest[1..];
+            }
+        }
+
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        match name.strip_prefix(ENV_PREFIX) {
+            Some(name) => self.env.get(name),
+            None => self.values.get(name),
+        }
+        .map(String::as_str)
+    }
+
+    fn evaluate(&self, expression: &str) -> Result<String, Error> {
+        let (name, default) = Self::split_default(expression);
+
+        self.get(name)
+            .or(default)
+            .map(String::from)
+            .ok_or_else(|| Error::UndefinedVariable(name.into()))
+    }
+
+    fn split_default(expression: &str) -> (&str, Option<&str>) {
+        match expression.split_once(":-") {
+            Some((name, default)) => (name.trim(), Some(default)),
+            None => (expression.trim(), None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::Vars;
+    use crate::config::Error;
+
+    fn vars() -> Vars {
+        Vars::new(
+            BTreeMap::from([("machine".into(), "laptop".into())]),
+            BTreeMap::from([("EDITOR".into(), "nano".into())]),
+        )
+    }
+
+    #[test]
+    fn interpolates_vars_and_env() {
+        assert_eq!(
+            "laptop/nano.conf",
+            vars().interpolate("${machine}/${env.EDITOR}.conf").unwrap()
+        );
+    }
+
+    #[test]
+    fn defaults_when_missing() {
+        assert_eq!(
+            "vim nano",
+            vars()
+                .interpolate("${env.VISUAL:-vim} ${env.EDITOR:-vim}")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn leaves_other_dollars_alone() {
+        assert_eq!(
+            "echo $HOME costs $5 ${literal}",
+            vars()
+                .interpolate("echo $HOME costs $5 $${literal}")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn undefined_variable() {
+        assert!(matches!(
+            vars().interpolate("${missing}"),
+            Err(Error::UndefinedVariable(name)) if name == "missing"
+        ));
+    }
+
+    #[test]
+    fn unterminated_variable() {
+        assert!(matches!(
+            vars().interpolate("${machine"),
+            Err(Error::UnterminatedVariable(input)) if input == "${machine"
+        ));
+    }
+
+    #[test]
+    fn scoped_vars_can_use_outer_vars_and_overrides_win() {
+        let vars = vars()
+            .with(BTreeMap::from([
+                ("dotfiles".into(), "~/${machine}".into()),
+                ("machine".into(), "desktop".into()),
+                ("previous".into(), "${machine:-none}".into()),
+            ]))
+            .unwrap()
+            .overridden_by(&BTreeMap::from([("machine".into(), "server".into())]));
+
+        assert_eq!(
+            "~/desktop server desktop",
+            vars.interpolate("${dotfiles} ${machine} ${previous}")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn scoped_vars_that_use_each_other_in_a_cycle() {
+        assert!(matches!(
+            vars().with(BTreeMap::from([
+                ("a".into(), "${b}".into()),
+                ("b".into(), "${a:-default}".into()),
+            ])),
+            Err(Error::VariableCycle(names)) if names == vec!["a", "b", "a"]
+        ));
+    }
+}
@@ -1,9 +1,151 @@
 use core::result::Result;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fmt::{Display, Formatter};
 use std::io;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::Duration;
 
 use thiserror::Error as ThisError;
 
+use crate::state;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Step {
+    pub name: String,
+    pub operation: Operation,
+    pub ignore_errors: bool,
+    pub when: Option<When>,
+    pub tags: BTreeSet<String>,
+    pub needs: Vec<String>,
+    pub origin: Option<Origin>,
+}
+
+impl Step {
+    /// A step with nothing but a name and an operation
+    #[cfg(test)]
+    pub(crate) fn new(name: &str, operation: Operation) -> Self {
+        Self {
+            name: name.into(),
+            operation,
+            ignore_errors: false,
+            when: None,
+            tags: BTreeSet::new(),
+            needs: Vec::new(),
+            origin: None,
+        }
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} {}",
+            self.name,
+            self.operation.kind(),
+            self.operation.summary()
+        )?;
+
+        if !self.tags.is_empty() {
+            write!(
+                f,
+                " [{}]",
+                self.tags.iter().cloned().collect::<Vec<_>>().join(", ")
+            )?;
+        }
+
+        if let Some(when) = &self.when {
+            write!(f, " (when {})", when.expression)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Selection {
+    pub tags: BTreeSet<String>,
+    pub skip_tags: BTreeSet<String>,
+    pub only: BTreeSet<String>,
+    pub start_at: Option<String>,
+}
+
+impl Selection {
+    pub fn includes(&self, step: &Step) -> bool {
+        (self.tags.is_empty() || !self.tags.is_disjoint(&step.tags))
+            && self.skip_tags.is_disjoint(&step.tags)
+            && (self.only.is_empty() || self.only.contains(&step.name))
+    }
+
+    pub fn select(&self, steps: Vec<Step>) -> impl Iterator<Item = Step> + '_ {
+        steps
+            .into_iter()
+            .skip_while(move |step| {
+                self.start_at
+                    .as_ref()
+                    .is_some_and(|start_at| start_at != &step.name)
+            })
+            .filter(move |step| self.includes(step))
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct When {
+    pub expression: String,
+    pub condition: Condition,
+}
+
+impl When {
+    pub fn and(&self, other: Self) -> Self {
+        Self {
+            expression: format!("({}) && ({})", self.expression, other.expression),
+            condition: Condition::And(Box::new(self.condition.clone()), Box::new(other.condition)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Condition {
+    Constant(bool),
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    CommandExists(String),
+    PathExists(PathBuf),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Origin {
+    pub file: PathBuf,
+    pub index: usize,
+    pub position: Option<Position>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.position {
+            Some(Position { line, column }) => write!(
+                f,
+                "todo entry {} at {}:{}:{}",
+                self.index,
+                self.file.display(),
+                line,
+                column
+            ),
+            None => write!(f, "todo entry {} in {}", self.index, self.file.display()),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Operation {
     Copy {
@@ -15,14 +157,33 @@ pub enum Operation {
         to: OperationPath,
         overwrite: bool,
     },
-    Exec {
+    Exec(Box<Exec>),
+    Shell {
         working_dir: PathBuf,
-        command: String,
-        args: Vec<String>,
+        command: OperationCommand,
     },
 }
 
 impl Operation {
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Copy { .. } => "copy",
+            Self::Link { .. } => "link",
+            Self::Exec(_) => "exec",
+            Self::Shell { .. } => "shell",
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        match self {
+            Self::Copy { to, .. } | Self::Link { to, .. } => to.location.display().to_string(),
+            Self::Exec(exec) => format!("{} {}", exec.command, exec.display_args())
+                .trim_end()
+                .to_string(),
+            Self::Shell { command, .. } => command.run.clone(),
+        }
+    }
+
     pub(crate) fn new_copy(home: &str, current_dir: &Path, to: &str, from: &str) -> Self {
         Self::Copy {
             from: OperationPath::new(current_dir, Path::new(home), from),
@@ -44,13 +205,123 @@ impl Operation {
         }
     }
 
-    pub(crate) fn new_exec(current_dir: &Path, command: String, args: Vec<String>) -> Self {
-        Self::Exec {
+    pub(crate) fn new_shell(
+        current_dir: &Path,
+        run: String,
+        shell: String,
+        undo: Option<String>,
+    ) -> Self {
+        Self::Shell {
             working_dir: current_dir.to_path_buf(),
+            command: OperationCommand { run, shell, undo },
+        }
+    }
+}
+
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Exec {
+    pub working_dir: PathBuf,
+    pub command: String,
+    pub args: Vec<String>,
+    pub allowed_exit_codes: Vec<i32>,
+    pub output: Output,
+    pub env: BTreeMap<String, String>,
+    pub clear_env: bool,
+    pub stdin: Option<Stdin>,
+    pub guards: Guards,
+    pub timeout: Option<Duration>,
+    pub retries: u32,
+    pub retry_delay: Duration,
+    pub undo: Option<Undo>,
+}
+
+/// The command that reverses an exec, run in the same working directory
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Undo {
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Guards {
+    pub creates: Option<PathBuf>,
+    pub removes: Option<PathBuf>,
+    pub unless: Option<String>,
+    pub onlyif: Option<String>,
+    pub shell: String,
+}
+
+impl Default for Guards {
+    fn default() -> Self {
+        Self {
+            creates: None,
+            removes: None,
+            unless: None,
+            onlyif: None,
+            shell: default_shell(),
+        }
+    }
+}
+
+/// The user's `SHELL`, or `/bin/sh` if it isn't set
+pub(crate) fn default_shell() -> String {
+    env::var("SHELL")
+        .ok()
+        .filter(|shell| !shell.is_empty())
+        .unwrap_or_else(|| "/bin/sh".into())
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Stdin {
+    Literal(String),
+    File(PathBuf),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Output {
+    Stream,
+    Capture,
+}
+
+impl Exec {
+    pub(crate) fn new(working_dir: &Path, command: String, args: Vec<String>) -> Self {
+        Self {
+            working_dir: working_dir.to_path_buf(),
             command,
             args,
+            allowed_exit_codes: vec![0],
+            output: Output::Stream,
+            env: BTreeMap::new(),
+            clear_env: false,
+            stdin: None,
+            guards: Guards::default(),
+            timeout: None,
+            retries: 0,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            undo: None,
         }
     }
+
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        self.retry_delay
+            .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+    }
+
+    pub(crate) fn is_success(&self, status: ExitStatus) -> bool {
+        status
+            .code()
+            .is_some_and(|code| self.allowed_exit_codes.contains(&code))
+    }
+
+    pub(crate) fn display_args(&self) -> String {
+        self.args
+            .iter()
+            .map(|arg| format!("\"{}\"", arg))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -62,6 +333,8 @@ pub struct OperationPath {
 pub struct OperationCommand {
     pub run: String,
     pub shell: String,
+    /// A script that reverses `run`, run with the same shell
+    pub undo: Option<String>,
 }
 
 impl OperationPath {
@@ -76,7 +349,7 @@ impl OperationPath {
 
     fn canonical_path(working_dir: &Path, from: &Path) -> PathBuf {
         if from.is_relative() {
-            working_dir.join(&from)
+            working_dir.join(from)
         } else {
             from.into()
         }
@@ -88,10 +361,26 @@ pub enum DriverTypes {
     Io,
 }
 
-pub trait Driver<NewSelf = Self> {
-    fn copy(self, from: &Path, to: &Path) -> Result<NewSelf, Error>;
-    fn link(self, from: &Path, to: &Path, overwrite: bool) -> Result<NewSelf, Error>;
-    fn exec(self, working_dir: &Path, command: &str, args: &[String]) -> Result<NewSelf, Error>;
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OperationOutcome {
+    Changed,
+    Unchanged,
+    Skipped,
+}
+
+pub trait Driver: Sync {
+    fn copy(&self, from: &Path, to: &Path) -> Result<OperationOutcome, Error>;
+    fn link(&self, from: &Path, to: &Path, overwrite: bool) -> Result<OperationOutcome, Error>;
+    fn exec(&self, label: &str, exec: &Exec) -> Result<OperationOutcome, Error>;
+    fn shell(
+        &self,
+        label: &str,
+        working_dir: &Path,
+        command: &OperationCommand,
+    ) -> Result<OperationOutcome, Error>;
+    fn remove(&self, path: &Path) -> Result<OperationOutcome, Error>;
+    fn restore(&self, backup: &Path, to: &Path) -> Result<OperationOutcome, Error>;
+    fn skip(&self, label: &str, kind: &str, reason: &str);
 }
 
 #[derive(ThisError, Debug)]
@@ -100,15 +389,306 @@ pub enum Error {
     Copy(PathBuf, PathBuf, #[source] io::Error),
     #[error("link from `{0}` to `{1}` failed")]
     Link(PathBuf, PathBuf, #[source] io::Error),
-    #[error("exec `{0} {1}` in {2} failed")]
-    Exec(String, String, PathBuf, #[source] io::Error),
+    #[error("exec `{0} {1}` in {2} could not be started")]
+    Spawn(String, String, PathBuf, #[source] io::Error),
+    #[error("exec `{0} {1}` could not read its stdin from `{2}`")]
+    Stdin(String, String, PathBuf, #[source] io::Error),
+    #[error("exec `{0} {1}` in {2} failed with {3}{}", stderr_suffix(.4))]
+    Exec(String, String, PathBuf, ExitStatus, String),
+    #[error(
+        "exec `{0} {1}` in {2} timed out after {} on attempt {4}",
+        humantime::format_duration(*.3)
+    )]
+    Timeout(String, String, PathBuf, Duration, u32),
+    #[error("removing `{0}` failed")]
+    Remove(PathBuf, #[source] io::Error),
+    #[error("backing up `{0}` to `{1}` failed")]
+    Backup(PathBuf, PathBuf, #[source] io::Error),
+    #[error("restoring `{1}` from `{0}` failed")]
+    Restore(PathBuf, PathBuf, #[source] io::Error),
+    #[error("could not record what was done")]
+    State(#[source] state::Error),
+    #[error("stopped unexpectedly: {0}")]
+    Panic(String),
+}
+
+impl Error {
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Self::Copy(from, _, error) if error.kind() == ErrorKind::NotFound && !from.exists() => {
+                Some(format!(
+                    "the source `{}` does not exist, check the `from` path",
+                    from.display()
+                ))
+            }
+            Self::Link(_, to, error) if error.kind() == ErrorKind::AlreadyExists => Some(format!(
+                "`{}` already exists, remove it or set `overwrite: true` to replace it",
+                to.display()
+            )),
+            Self::Copy(_, to, error) | Self::Link(_, to, error)
+                if error.kind() == ErrorKind::PermissionDenied =>
+            {
+                Some(format!(
+                    "you do not have permission to write `{}`, check the ownership of it and its parent directories",
+                    to.display()
+                ))
+            }
+            Self::Spawn(_, _, working_dir, error)
+                if error.kind() == ErrorKind::NotFound && !working_dir.is_dir() =>
+            {
+                Some(format!(
+                    "the working directory `{}` does not exist",
+                    working_dir.display()
+                ))
+            }
+            Self::Spawn(command, _, _, error) if error.kind() == ErrorKind::NotFound => {
+                Some(format!(
+                    "`{}` was not found, check it is installed and on your PATH",
+                    command
+                ))
+            }
+            Self::Spawn(command, _, _, error) if error.kind() == ErrorKind::PermissionDenied => {
+                Some(format!("`{}` is not executable", command))
+            }
+            Self::Stdin(_, _, path, error) if error.kind() == ErrorKind::NotFound => {
+                Some(format!(
+                    "the stdin file `{}` does not exist, check the `stdin` `file` path",
+                    path.display()
+                ))
+            }
+            Self::Stdin(_, _, path, error) if error.kind() == ErrorKind::PermissionDenied => {
+                Some(format!(
+                    "you do not have permission to read `{}`",
+                    path.display()
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn stderr_suffix(stderr: &str) -> String {
+    if stderr.is_empty() {
+        String::new()
+    } else {
+        format!(": {}", stderr)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io;
+    use std::io::ErrorKind;
+    use std::os::unix::process::ExitStatusExt;
     use std::path::Path;
+    use std::process::ExitStatus;
+    use std::time::Duration;
+
+    use crate::domain::{Condition, Error, Exec, Operation, OperationPath, Selection, Step, When};
+
+    fn exec(allowed_exit_codes: Vec<i32>) -> Exec {
+        Exec {
+            allowed_exit_codes,
+            ..Exec::new(Path::new("/tmp"), "false".into(), vec![])
+        }
+    }
+
+    fn tagged(tags: &[&str]) -> Step {
+        named("step", tags)
+    }
+
+    fn named(name: &str, tags: &[&str]) -> Step {
+        Step {
+            tags: tags.iter().map(|tag| String::from(*tag)).collect(),
+            ..Step::new(
+                name,
+                Operation::new_shell(Path::new("/tmp"), "true".into(), "/bin/sh".into(), None),
+            )
+        }
+    }
+
+    fn selection(tags: &[&str], skip_tags: &[&str]) -> Selection {
+        Selection {
+            tags: tags.iter().map(|tag| String::from(*tag)).collect(),
+            skip_tags: skip_tags.iter().map(|tag| String::from(*tag)).collect(),
+            ..Selection::default()
+        }
+    }
+
+    fn selected_names(selection: &Selection) -> Vec<String> {
+        selection
+            .select(vec![
+                named("1", &[]),
+                named("git", &["git"]),
+                named("3", &[]),
+                named("gui", &["gui"]),
+            ])
+            .map(|step| step.name)
+            .collect()
+    }
+
+    #[test]
+    fn everything_is_selected_by_default() {
+        assert!(Selection::default().includes(&tagged(&[])));
+        assert!(Selection::default().includes(&tagged(&["gui"])));
+    }
 
-    use crate::domain::OperationPath;
+    #[test]
+    fn tags_select_steps_with_any_of_them() {
+        assert!(selection(&["git", "shell"], &[]).includes(&tagged(&["shell", "work"])));
+        assert!(!selection(&["git"], &[]).includes(&tagged(&["shell"])));
+        assert!(!selection(&["git"], &[]).includes(&tagged(&[])));
+    }
+
+    #[test]
+    fn skip_tags_win() {
+        assert!(!selection(&["shell"], &["gui"]).includes(&tagged(&["shell", "gui"])));
+        assert!(selection(&[], &["gui"]).includes(&tagged(&["shell"])));
+    }
+
+    #[test]
+    fn step_display() {
+        let mut step = named("zsh", &["shell", "work"]);
+        step.when = Some(When {
+            expression: "os == \"linux\"".into(),
+            condition: Condition::Constant(true),
+        });
+
+        assert_eq!(
+            "zsh: shell true [shell, work] (when os == \"linux\")",
+            step.to_string()
+        );
+        assert_eq!("step: shell true", tagged(&[]).to_string());
+    }
+
+    #[test]
+    fn only_selects_named_steps() {
+        let selection = Selection {
+            only: ["git".into(), "3".into()].into(),
+            ..Selection::default()
+        };
+
+        assert_eq!(vec!["git", "3"], selected_names(&selection));
+    }
+
+    #[test]
+    fn start_at_skips_earlier_steps() {
+        let selection = Selection {
+            start_at: Some("git".into()),
+            skip_tags: ["gui".into()].into(),
+            ..Selection::default()
+        };
+
+        assert_eq!(vec!["git", "3"], selected_names(&selection));
+    }
+
+    #[test]
+    fn exit_code_is_allowed() {
+        assert!(exec(vec![0, 1]).is_success(ExitStatus::from_raw(1 << 8)));
+    }
+
+    #[test]
+    fn exit_code_is_not_allowed() {
+        assert!(!exec(vec![0]).is_success(ExitStatus::from_raw(1 << 8)));
+    }
+
+    #[test]
+    fn backoff_doubles_each_attempt() {
+        let exec = Exec {
+            retry_delay: Duration::from_secs(2),
+            ..Exec::new(Path::new("/tmp"), "false".into(), vec![])
+        };
+
+        assert_eq!(Duration::from_secs(2), exec.backoff(1));
+        assert_eq!(Duration::from_secs(4), exec.backoff(2));
+        assert_eq!(Duration::from_secs(8), exec.backoff(3));
+    }
+
+    #[test]
+    fn exec_failure_without_stderr() {
+        assert_eq!(
+            "exec `false ` in /tmp failed with exit status: 1",
+            Error::Exec(
+                "false".into(),
+                String::new(),
+                "/tmp".into(),
+                ExitStatus::from_raw(1 << 8),
+                String::new()
+            )
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn hint_for_missing_copy_source() {
+        let error = Error::Copy(
+            "/does/not/exist.txt".into(),
+            "/tmp/out.txt".into(),
+            io::Error::from(ErrorKind::NotFound),
+        );
+
+        assert_eq!(
+            Some("the source `/does/not/exist.txt` does not exist, check the `from` path".into()),
+            error.hint()
+        );
+    }
+
+    #[test]
+    fn hint_for_existing_link_destination() {
+        let error = Error::Link(
+            "/tmp/in.txt".into(),
+            "/tmp/out.txt".into(),
+            io::Error::from(ErrorKind::AlreadyExists),
+        );
+
+        assert_eq!(
+            Some(
+                "`/tmp/out.txt` already exists, remove it or set `overwrite: true` to replace it"
+                    .into()
+            ),
+            error.hint()
+        );
+    }
+
+    #[test]
+    fn hint_for_missing_command() {
+        let error = Error::Spawn(
+            "not-a-real-command".into(),
+            String::new(),
+            "/tmp".into(),
+            io::Error::from(ErrorKind::NotFound),
+        );
+
+        assert_eq!(
+            Some(
+                "`not-a-real-command` was not found, check it is installed and on your PATH".into()
+            ),
+            error.hint()
+        );
+    }
+
+    #[test]
+    fn hint_for_missing_stdin_file() {
+        let error = Error::Stdin(
+            "cat".into(),
+            String::new(),
+            "/does/not/exist.txt".into(),
+            io::Error::from(ErrorKind::NotFound),
+        );
+
+        assert_eq!(
+            Some(
+                "the stdin file `/does/not/exist.txt` does not exist, check the `stdin` `file` path"
+                    .into()
+            ),
+            error.hint()
+        );
+    }
+
+    #[test]
+    fn killed_by_signal_is_not_allowed() {
+        assert!(!exec(vec![0]).is_success(ExitStatus::from_raw(9)));
+    }
 
     #[test]
     fn relative_canonical_to() {
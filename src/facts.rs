@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::CStr;
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+const PREFIX: &str = "facts.";
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct Facts {
+    pub hostname: String,
+    pub username: String,
+    pub uid: u32,
+    pub os: String,
+    pub kernel: String,
+    pub arch: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distro: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    pub container: bool,
+    pub wsl: bool,
+}
+
+impl Facts {
+    pub fn gather() -> Self {
+        let uname = uname();
+        let kernel = uname
+            .as_ref()
+            .map(|(_, release)| release.clone())
+            .unwrap_or_default();
+
+        Self {
+            hostname: uname.map(|(hostname, _)| hostname).unwrap_or_default(),
+            username: username(),
+            // SAFETY: getuid has no preconditions and cannot fail
+            uid: unsafe { libc::getuid() },
+            os: env::consts::OS.into(),
+            arch: env::consts::ARCH.into(),
+            distro: fs::read_to_string("/etc/os-release")
+                .ok()
+                .and_then(|os_release| parse_os_release_id(&os_release)),
+            shell: env::var("SHELL").ok().filter(|shell| !shell.is_empty()),
+            container: is_container(),
+            wsl: env::var_os("WSL_DISTRO_NAME").is_some()
+                || kernel.to_lowercase().contains("microsoft"),
+            kernel,
+        }
+    }
+
+    pub fn to_vars(&self) -> BTreeMap<String, String> {
+        let mut vars = BTreeMap::from([
+            ("hostname", self.hostname.clone()),
+            ("username", self.username.clone()),
+            ("uid", self.uid.to_string()),
+            ("os", self.os.clone()),
+            ("kernel", self.kernel.clone()),
+            ("arch", self.arch.clone()),
+            ("container", self.container.to_string()),
+            ("wsl", self.wsl.to_string()),
+        ]);
+
+        if let Some(distro) = &self.distro {
+            vars.insert("distro", distro.clone());
+        }
+
+        if let Some(shell) = &self.shell {
+            vars.insert("shell", shell.clone());
+        }
+
+        vars.into_iter()
+            .map(|(name, value)| (format!("{}{}", PREFIX, name), value))
+            .collect()
+    }
+}
+
+fn uname() -> Option<(String, String)> {
+    // SAFETY: utsname is plain data, and uname fills it with nul terminated
+    // strings when it succeeds
+    unsafe {
+        let mut uname: libc::utsname = std::mem::zeroed();
+        if libc::uname(&mut uname) != 0 {
+            return None;
+        }
+
+        Some((
+            CStr::from_ptr(uname.nodename.as_ptr())
+                .to_string_lossy()
+                .into_owned(),
+            CStr::from_ptr(uname.release.as_ptr())
+                .to_string_lossy()
+                .into_owned(),
+        ))
+    }
+}
+
+fn username() -> String {
+    // SAFETY: getpwuid returns either null or a pointer to a passwd entry
+    // that stays valid until the next call, and it is copied out straight away
+    let from_passwd = unsafe {
+        let passwd = libc::getpwuid(libc::getuid());
+        if passwd.is_null() || (*passwd).pw_name.is_null() {
+            None
+        } else {
+            Some(
+                CStr::from_ptr((*passwd).pw_name)
+                    .to_string_lossy()
+                    .into_owned(),
+            )
+        }
+    };
+
+    from_passwd
+        .or_else(|| env::var("USER").ok())
+        .unwrap_or_default()
+}
+
+fn is_container() -> bool {
+    Path::new("/.dockerenv").exists()
+        || Path::new("/run/.containerenv").exists()
+        || env::var_os("container").is_some()
+        || fs::read_to_string("/proc/1/cgroup").is_ok_and(|cgroup| {
+            ["docker", "containerd", "kubepods", "lxc", "podman"]
+                .iter()
+                .any(|runtime| cgroup.contains(runtime))
+        })
+}
+
+fn parse_os_release_id(os_release: &str) -> Option<String> {
+    os_release
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("ID="))
+        .map(|id| id.trim_matches(|c| c == '"' || c == '\'').to_string())
+        .find(|id| !id.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use indoc::indoc;
+
+    use super::{parse_os_release_id, Facts};
+
+    #[test]
+    fn distro_from_os_release() {
+        assert_eq!(
+            Some("ubuntu".into()),
+            parse_os_release_id(indoc! {r#"
+                NAME="Ubuntu"
+                VERSION_ID="22.04"
+                ID=ubuntu
+                ID_LIKE=debian
+            "#})
+        );
+    }
+
+    #[test]
+    fn quoted_distro_from_os_release() {
+        assert_eq!(
+            Some("opensuse-tumbleweed".into()),
+            parse_os_release_id("ID=\"opensuse-tumbleweed\"\n")
+        );
+    }
+
+    #[test]
+    fn no_distro_in_os_release() {
+        assert_eq!(None, parse_os_release_id("NAME=Something\n"));
+    }
+
+    #[test]
+    fn facts_as_vars() {
+        let facts = Facts {
+            hostname: "laptop".into(),
+            username: "billie".into(),
+            uid: 1000,
+            os: "linux".into(),
+            kernel: "6.1.0".into(),
+            arch: "x86_64".into(),
+            distro: Some("debian".into()),
+            shell: None,
+            container: false,
+            wsl: true,
+        };
+
+        assert_eq!(
+            BTreeMap::from([
+                ("facts.arch".into(), "x86_64".into()),
+                ("facts.container".into(), "false".into()),
+                ("facts.distro".into(), "debian".into()),
+                ("facts.hostname".into(), "laptop".into()),
+                ("facts.kernel".into(), "6.1.0".into()),
+                ("facts.os".into(), "linux".into()),
+                ("facts.uid".into(), "1000".into()),
+                ("facts.username".into(), "billie".into()),
+                ("facts.wsl".into(), "true".into()),
+            ]),
+            facts.to_vars()
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use thiserror::Error as ThisError;
+
+use crate::domain::Step;
+
+pub fn sort(steps: Vec<Step>) -> Result<Vec<Step>, Error> {
+    let positions: BTreeMap<&str, usize> = steps
+        .iter()
+        .enumerate()
+        .map(|(position, step)| (step.name.as_str(), position))
+        .collect();
+
+    let mut needs = Vec::with_capacity(steps.len());
+    for step in &steps {
+        needs.push(
+            step.needs
+                .iter()
+                .map(|need| {
+                    positions
+                        .get(need.as_str())
+                        .copied()
+                        .ok_or_else(|| Error::UnknownNeed(step.name.clone(), need.clone()))
+                })
+                .collect::<Result<BTreeSet<_>, _>>()?,
+        );
+    }
+
+    let mut order = Vec::with_capacity(steps.len());
+    let mut done = vec![false; steps.len()];
+    while order.len() < steps.len() {
+        let next = (0..steps.len())
+            .find(|&position| !done[position] && needs[position].iter().all(|&need| done[need]))
+            .ok_or_else(|| Error::Cycle(cycle(&steps, &needs, &done)))?;
+
+        done[next] = true;
+        order.push(next);
+    }
+
+    let mut steps: Vec<Option<Step>> = steps.into_iter().map(Some).collect();
+    Ok(order
+        .into_iter()
+        .filter_map(|position| steps[position].take())
+        .collect())
+}
+
+fn cycle(steps: &[Step], needs: &[BTreeSet<usize>], done: &[bool]) -> Vec<String> {
+    let mut path = vec![(0..steps.len())
+        .find(|&position| !done[position])
+        .unwrap_or_default()];
+
+    loop {
+        let current = path[path.len() - 1];
+        let next = needs[current]
+            .iter()
+            .copied()
+            .find(|&need| !done[need])
+            .unwrap_or(current);
+
+        if let Some(start) = path.iter().position(|&position| position == next) {
+            return path[start..]
+                .iter()
+                .chain(std::iter::once(&next))
+                .map(|&position| steps[position].name.clone())
+                .collect();
+        }
+
+        path.push(next);
+    }
+}
+
+pub fn dot(steps: &[Step]) -> String {
+    let mut dot = String::from("digraph ellipsis {\n");
+
+    for step in steps {
+        dot.push_str(&format!(
+            "    {} [label={}];\n",
+            quote(&step.name),
+            quote(&format!(
+                "{}: {} {}",
+                step.name,
+                step.operation.kind(),
+                step.operation.summary()
+            ))
+        ));
+    }
+
+    for step in steps {
+        for need in &step.needs {
+            dot.push_str(&format!("    {} -> {};\n", quote(need), quote(&step.name)));
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+fn quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[derive(ThisError, Debug)]
+pub enum Error {
+    #[error("`{0}` needs `{1}`, but there is no operation with that name")]
+    UnknownNeed(String, String),
+    #[error("operations need each other in a cycle: {}", .0.join(" needs "))]
+    Cycle(Vec<String>),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{dot, sort, Error};
+    use crate::domain::{Operation, Step};
+
+    fn step(name: &str, needs: &[&str]) -> Step {
+        Step {
+            needs: needs.iter().map(|need| String::from(*need)).collect(),
+            ..Step::new(
+                name,
+                Operation::new_shell(Path::new("/tmp"), "true".into(), "/bin/sh".into(), None),
+            )
+        }
+    }
+
+    fn names(steps: Vec<Step>) -> Vec<String> {
+        steps.into_iter().map(|step| step.name).collect()
+    }
+
+    #[test]
+    fn keeps_the_order_without_needs() {
+        assert_eq!(
+            vec!["a", "b", "c"],
+            names(sort(vec![step("a", &[]), step("b", &[]), step("c", &[])]).unwrap())
+        );
+    }
+
+    #[test]
+    fn moves_steps_after_what_they_need() {
+        assert_eq!(
+            vec!["b", "c", "a", "d"],
+            names(
+                sort(vec![
+                    step("a", &["c"]),
+                    step("b", &[]),
+                    step("c", &["b"]),
+                    step("d", &[]),
+                ])
+                .unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_needs_are_an_error() {
+        assert!(matches!(
+            sort(vec![step("a", &["missing"])]),
+            Err(Error::UnknownNeed(step, need)) if step == "a" && need == "missing"
+        ));
+    }
+
+    #[test]
+    fn cycles_are_an_error() {
+        let error = sort(vec![
+            step("a", &[]),
+            step("b", &["d"]),
+            step("c", &["b"]),
+            step("d", &["c"]),
+        ])
+        .unwrap_err();
+
+        assert_eq!(
+            "operations need each other in a cycle: b needs d needs c needs b",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn renders_dot() {
+        assert_eq!(
+            concat!(
+                "digraph ellipsis {\n",
+                "    \"a\" [label=\"a: shell true\"];\n",
+                "    \"b \\\"quoted\\\"\" [label=\"b \\\"quoted\\\": shell true\"];\n",
+                "    \"a\" -> \"b \\\"quoted\\\"\";\n",
+                "}\n",
+            ),
+            dot(&[step("a", &[]), step("b \"quoted\"", &["a"])])
+        );
+    }
+}
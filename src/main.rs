@@ -1,38 +1,198 @@
 use std::convert::TryFrom;
-
-use thiserror::Error as ThisError;
+use std::env;
+use std::fmt::Display;
+use std::io;
+use std::io::{ErrorKind, IsTerminal, Write};
+use std::path::Path;
 
 mod cli;
 mod config;
 mod domain;
+mod facts;
+mod graph;
 mod operations;
+mod state;
 use anyhow::Result as AnyhowResult;
 
 fn main() -> AnyhowResult<()> {
     let matches = cli::cli().get_matches();
+
+    if let Some(("facts", facts_matches)) = matches.subcommand() {
+        let facts = facts::Facts::gather();
+
+        match facts_matches.value_of("format") {
+            Some("json") => print(format_args!("{}\n", serde_json::to_string_pretty(&facts)?))?,
+            _ => print(serde_yaml::to_string(&facts)?)?,
+        }
+
+        return Ok(());
+    }
+
+    if let Some(("uninstall", _)) = matches.subcommand() {
+        let store = open_state(&matches)?;
+        let known = store.state();
+        let report = if matches.is_present("dry-run") {
+            let driver = operations::BlackholeDriver::new();
+            let report = operations::uninstall(&known, &driver, None);
+
+            for (operation, message) in driver.into_log() {
+                print(format_args!("{}: {}\n", operation, message))?;
+            }
+
+            report
+        } else {
+            operations::uninstall(&known, &operations::IoDriver::new(), Some(&store))
+        };
+
+        return finish(&report);
+    }
+
+    if matches.is_present("which-config") {
+        print(format_args!("{}\n", config::location(&matches)?.display()))?;
+        return Ok(());
+    }
+
     let config = config::Config::try_from(&matches)?;
 
-    match config.driver {
+    match matches.subcommand() {
+        Some(("list", _)) => {
+            for step in config.selection.select(config.steps) {
+                print(format_args!("{}\n", step))?;
+            }
+
+            return Ok(());
+        }
+        Some(("graph", _)) => {
+            print(graph::dot(&config.steps))?;
+
+            return Ok(());
+        }
+        Some(("diff", _)) => {
+            print(operations::diff(
+                config
+                    .selection
+                    .select(config.steps)
+                    .collect::<Vec<_>>()
+                    .iter(),
+            ))?;
+
+            return Ok(());
+        }
+        Some(("status", status_matches)) => {
+            let statuses = operations::status(
+                config
+                    .selection
+                    .select(config.steps)
+                    .collect::<Vec<_>>()
+                    .iter(),
+                &open_state(&matches)?.state(),
+            );
+
+            match status_matches.value_of("format") {
+                Some("json") => print(format_args!(
+                    "{}\n",
+                    serde_json::to_string_pretty(&statuses)?
+                ))?,
+                _ => print(operations::table(
+                    &statuses,
+                    io::stdout().is_terminal()
+                        && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+                ))?,
+            }
+
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let prune = matches.is_present("prune");
+    let wanted = operations::targets(&config.steps);
+    let store = open_state(&matches)?;
+    let known = store.state();
+
+    let report = match config.driver {
         domain::DriverTypes::Io => {
-            operations::run(config, operations::IoDriver::new())?;
+            let driver = operations::IoDriver::new().with_backups(operations::Backups {
+                dir: store.backups_dir(),
+                known: known.clone(),
+            });
+            let driver = if config.jobs > 1 {
+                driver.with_grouped_output()
+            } else {
+                driver
+            };
+
+            let report = operations::run(config, &driver, Some(&store));
+
+            if prune {
+                operations::prune_after(report, &wanted, &store.state(), &driver, Some(&store))
+            } else {
+                report
+            }
         }
 
         domain::DriverTypes::Blackhole => {
-            let driver = operations::run(config, operations::BlackholeDriver::new())?;
+            let driver = if matches.is_present("diff") {
+                operations::BlackholeDriver::new().with_diffs()
+            } else {
+                operations::BlackholeDriver::new()
+            };
+            let mut report = operations::run(config, &driver, None);
 
-            for (operation, message) in driver.log {
-                println!("{}: {}", operation, message);
+            if prune {
+                report = operations::prune_after(report, &wanted, &known, &driver, None);
             }
+
+            for (operation, message) in driver.into_log() {
+                print(format_args!("{}: {}\n", operation, message))?;
+            }
+
+            report
+        }
+    };
+
+    if prune {
+        let config_file = env::current_dir()?.join(config::location(&matches)?);
+        let dotfiles_dir = config_file.parent().unwrap_or(&config_file);
+        let dirs = wanted
+            .iter()
+            .chain(
+                known
+                    .entries
+                    .iter()
+                    .filter_map(|entry| entry.target.as_ref()),
+            )
+            .filter_map(|target| target.parent());
+
+        for dangling in operations::dangling(dirs, dotfiles_dir) {
+            print(format_args!("{}\n", dangling))?;
         }
     }
 
-    Ok(())
+    finish(&report)
 }
 
-#[derive(ThisError, Debug)]
-pub enum Error {
-    #[error("cli argument error")]
-    CliArgument(#[from] config::Error),
-    #[error("operation runner error")]
-    Runner(#[from] operations::RunnerError),
+fn open_state(matches: &clap::ArgMatches) -> AnyhowResult<state::Store> {
+    Ok(state::Store::open(&state::location(Path::new(
+        matches.value_of("home").unwrap(),
+    )))?)
+}
+
+fn finish(report: &operations::Report) -> AnyhowResult<()> {
+    for failure in &report.failures {
+        eprintln!("{}", failure);
+    }
+
+    print(format_args!("{}\n", report.summary))?;
+
+    Ok(report.result()?)
+}
+
+/// Write to stdout, taking a reader that stopped early, like `head`, as
+/// having read all it wanted
+fn print(text: impl Display) -> AnyhowResult<()> {
+    match write!(io::stdout(), "{}", text) {
+        Err(error) if error.kind() != ErrorKind::BrokenPipe => Err(error.into()),
+        _ => Ok(()),
+    }
 }
@@ -1,65 +1,167 @@
 use std::path::Path;
+use std::sync::Mutex;
 
 use crate::domain;
-use crate::domain::Error;
+use crate::domain::{Error, Exec, OperationCommand, OperationOutcome};
+use crate::operations::{diff, guard, inspect};
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug)]
 pub struct Driver {
-    pub log: Vec<(String, String)>,
+    log: Mutex<Vec<(String, String)>>,
+    show_diffs: bool,
 }
 
 impl Driver {
-    #[allow(dead_code)]
     pub const fn new() -> Self {
-        Self { log: Vec::new() }
+        Self {
+            log: Mutex::new(Vec::new()),
+            show_diffs: false,
+        }
     }
 
-    pub fn log(&mut self, kind: String, message: String) {
-        self.log.push((kind, message));
+    /// Follow each copy and link with what it would change
+    pub const fn with_diffs(mut self) -> Self {
+        self.show_diffs = true;
+        self
+    }
+
+    fn with_diff<F>(&self, message: String, diff: F) -> String
+    where
+        F: FnOnce() -> Option<String>,
+    {
+        if !self.show_diffs {
+            return message;
+        }
+
+        match diff() {
+            Some(diff) => format!("{}\n{}", message, diff.trim_end()),
+            None => message,
+        }
+    }
+
+    pub fn log(&self, kind: String, message: String) {
+        self.log
+            .lock()
+            .expect("blackhole log poisoned")
+            .push((kind, message));
+    }
+
+    pub fn into_log(self) -> Vec<(String, String)> {
+        self.log.into_inner().expect("blackhole log poisoned")
     }
 }
 
 impl domain::Driver for Driver {
-    fn copy(mut self, from: &Path, to: &Path) -> Result<Self, domain::Error> {
-        self.log("copy".into(), format!("{:?} -> {:?}", from, to));
-        Ok(self)
+    fn copy(&self, from: &Path, to: &Path) -> Result<OperationOutcome, domain::Error> {
+        self.log(
+            "copy".into(),
+            self.with_diff(format!("{:?} -> {:?}", from, to), || {
+                diff::copy_diff(from, to)
+            }),
+        );
+
+        if inspect::has_same_contents(from, to) {
+            Ok(OperationOutcome::Unchanged)
+        } else {
+            Ok(OperationOutcome::Changed)
+        }
     }
 
-    fn link(mut self, from: &Path, to: &Path, overwrite: bool) -> Result<Self, Error> {
+    fn link(&self, from: &Path, to: &Path, overwrite: bool) -> Result<OperationOutcome, Error> {
         self.log(
             "link".into(),
-            format!("{:?} -> (overwriting: {}) {:?}", from, overwrite, to),
+            self.with_diff(
+                format!("{:?} -> (overwriting: {}) {:?}", from, overwrite, to),
+                || diff::link_diff(from, to),
+            ),
         );
-        Ok(self)
+
+        if inspect::is_link_to(from, to) {
+            Ok(OperationOutcome::Unchanged)
+        } else {
+            Ok(OperationOutcome::Changed)
+        }
     }
 
-    fn exec(
-        mut self,
+    fn exec(&self, _label: &str, exec: &Exec) -> Result<OperationOutcome, Error> {
+        let message = format!(
+            "in {:?} {:?} {:?}",
+            exec.working_dir, exec.command, exec.args
+        );
+
+        if let Some(reason) = guard::path_skip_reason(exec) {
+            self.log("exec".into(), format!("{} (skipped: {})", message, reason));
+            return Ok(OperationOutcome::Skipped);
+        }
+
+        // Checks are the user's own commands, so a dry run doesn't run them
+        let checks = [
+            ("unless", &exec.guards.unless),
+            ("onlyif", &exec.guards.onlyif),
+        ]
+        .iter()
+        .filter_map(|(guard, check)| check.as_ref().map(|check| format!("{} `{}`", guard, check)))
+        .collect::<Vec<_>>();
+
+        if checks.is_empty() {
+            self.log("exec".into(), message);
+        } else {
+            self.log(
+                "exec".into(),
+                format!("{} (would check {} first)", message, checks.join(" and ")),
+            );
+        }
+
+        Ok(OperationOutcome::Changed)
+    }
+
+    fn shell(
+        &self,
+        _label: &str,
         working_dir: &Path,
-        command: &str,
-        args: &[std::string::String],
-    ) -> Result<Self, Error> {
+        command: &OperationCommand,
+    ) -> Result<OperationOutcome, Error> {
         self.log(
-            "exec".into(),
-            format!("in {:?} {:?} {:?}", working_dir, command, args),
+            "shell".into(),
+            format!("in {:?} {:?} {:?}", working_dir, command.shell, command.run),
         );
-        Ok(self)
+        Ok(OperationOutcome::Changed)
+    }
+
+    fn remove(&self, path: &Path) -> Result<OperationOutcome, Error> {
+        self.log("remove".into(), format!("{:?}", path));
+        Ok(OperationOutcome::Changed)
+    }
+
+    fn restore(&self, backup: &Path, to: &Path) -> Result<OperationOutcome, Error> {
+        self.log("restore".into(), format!("{:?} -> {:?}", backup, to));
+        Ok(OperationOutcome::Changed)
+    }
+
+    fn skip(&self, _label: &str, kind: &str, reason: &str) {
+        self.log(kind.into(), format!("(skipped: {})", reason));
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
+
     use super::Driver as BlackholeDriver;
-    use crate::domain::Driver;
+    use crate::domain::{Driver, Exec, OperationCommand, OperationOutcome};
 
     #[test]
     fn exec_file() {
         let working_dir = tempfile::tempdir().unwrap().into_path();
-        let driver = BlackholeDriver::new()
+        let driver = BlackholeDriver::new();
+        driver
             .exec(
-                &working_dir,
-                "bash",
-                &["-c".into(), "echo hello > out.txt".into()],
+                "1",
+                &Exec::new(
+                    &working_dir,
+                    "bash".into(),
+                    vec!["-c".into(), "echo hello > out.txt".into()],
+                ),
             )
             .unwrap();
 
@@ -71,14 +173,92 @@ mod tests {
                     working_dir.display()
                 )
             )],
-            driver.log
+            driver.into_log()
+        );
+    }
+
+    #[test]
+    fn exec_that_is_guarded() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = Exec::new(&working_dir, "make".into(), vec!["install".into()]);
+        exec.guards.creates = Some(working_dir.clone());
+
+        let driver = BlackholeDriver::new();
+        let outcome = driver.exec("1", &exec).unwrap();
+
+        assert_eq!(OperationOutcome::Skipped, outcome);
+        assert_eq!(
+            vec![(
+                String::from("exec"),
+                format!(
+                    "in {:?} \"make\" [\"install\"] (skipped: {} exists)",
+                    working_dir,
+                    working_dir.display()
+                )
+            )],
+            driver.into_log()
         );
     }
 
+    #[test]
+    fn exec_with_checks_does_not_run_them() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = Exec::new(&working_dir, "make".into(), vec!["install".into()]);
+        exec.guards.unless = Some("touch unless.txt".into());
+        exec.guards.onlyif = Some("touch onlyif.txt".into());
+
+        let driver = BlackholeDriver::new();
+        let outcome = driver.exec("1", &exec).unwrap();
+
+        assert_eq!(OperationOutcome::Changed, outcome);
+        assert_eq!(
+            vec![(
+                String::from("exec"),
+                format!(
+                    "in {:?} \"make\" [\"install\"] (would check unless `touch unless.txt` and onlyif `touch onlyif.txt` first)",
+                    working_dir
+                )
+            )],
+            driver.into_log()
+        );
+        assert!(!working_dir.join("unless.txt").exists());
+        assert!(!working_dir.join("onlyif.txt").exists());
+    }
+
+    #[test]
+    fn shell_script() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let driver = BlackholeDriver::new();
+        driver
+            .shell(
+                "1",
+                &working_dir,
+                &OperationCommand {
+                    run: "echo hello > out.txt\n".into(),
+                    shell: "bash".into(),
+                    undo: None,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            vec![(
+                String::from("shell"),
+                format!(
+                    "in {:?} \"bash\" \"echo hello > out.txt\\n\"",
+                    working_dir.display()
+                )
+            )],
+            driver.into_log()
+        );
+        assert!(!working_dir.join("out.txt").exists());
+    }
+
     #[test]
     fn copy_file() {
         let working_dir = tempfile::tempdir().unwrap().into_path();
-        let driver = BlackholeDriver::new()
+        let driver = BlackholeDriver::new();
+        driver
             .copy(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
             .unwrap();
 
@@ -91,14 +271,15 @@ mod tests {
                     working_dir.join("out.txt")
                 )
             )],
-            driver.log
+            driver.into_log()
         );
     }
 
     #[test]
     fn link_file() {
         let working_dir = tempfile::tempdir().unwrap().into_path();
-        let driver = BlackholeDriver::new()
+        let driver = BlackholeDriver::new();
+        driver
             .link(
                 &working_dir.join("in.txt"),
                 &working_dir.join("out.txt"),
@@ -115,7 +296,68 @@ mod tests {
                     working_dir.join("out.txt")
                 )
             )],
-            driver.log
+            driver.into_log()
+        );
+    }
+
+    #[test]
+    fn link_file_that_is_already_linked() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        std::os::unix::fs::symlink(working_dir.join("in.txt"), working_dir.join("out.txt"))
+            .unwrap();
+
+        let outcome = BlackholeDriver::new()
+            .link(
+                &working_dir.join("in.txt"),
+                &working_dir.join("out.txt"),
+                false,
+            )
+            .unwrap();
+
+        assert_eq!(OperationOutcome::Unchanged, outcome);
+    }
+
+    #[test]
+    fn copy_file_that_would_change() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        fs::write(working_dir.join("out.txt"), "I exist").unwrap();
+
+        let outcome = BlackholeDriver::new()
+            .copy(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+            .unwrap();
+
+        assert_eq!(OperationOutcome::Changed, outcome);
+        assert_eq!(
+            "I exist",
+            fs::read_to_string(working_dir.join("out.txt")).unwrap()
+        );
+    }
+
+    #[test]
+    fn copy_file_with_diff() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!\n").unwrap();
+        fs::write(working_dir.join("out.txt"), "I exist\n").unwrap();
+
+        let driver = BlackholeDriver::new().with_diffs();
+        driver
+            .copy(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+            .unwrap();
+
+        assert_eq!(
+            vec![(
+                String::from("copy"),
+                format!(
+                    "{:?} -> {:?}\n--- {}\n+++ {}\n@@ -1 +1 @@\n-I exist\n+Hello, World!",
+                    working_dir.join("in.txt"),
+                    working_dir.join("out.txt"),
+                    working_dir.join("out.txt").display(),
+                    working_dir.join("in.txt").display()
+                )
+            )],
+            driver.into_log()
         );
     }
 }
@@ -0,0 +1,72 @@
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use crate::domain::Condition;
+
+pub fn holds(condition: &Condition) -> bool {
+    match condition {
+        Condition::Constant(value) => *value,
+        Condition::Not(condition) => !holds(condition),
+        Condition::And(left, right) => holds(left) && holds(right),
+        Condition::Or(left, right) => holds(left) || holds(right),
+        Condition::CommandExists(command) => command_exists(command),
+        Condition::PathExists(path) => fs::symlink_metadata(path).is_ok(),
+    }
+}
+
+fn command_exists(command: &str) -> bool {
+    if command.contains('/') {
+        return is_executable(Path::new(command));
+    }
+
+    env::var_os("PATH").is_some_and(|path| {
+        env::split_paths(&path).any(|directory| is_executable(&directory.join(command)))
+    })
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::holds;
+    use crate::domain::Condition;
+
+    #[test]
+    fn constants_and_logic() {
+        let condition = Condition::Or(
+            Box::new(Condition::And(
+                Box::new(Condition::Constant(true)),
+                Box::new(Condition::Constant(false)),
+            )),
+            Box::new(Condition::Not(Box::new(Condition::Constant(false)))),
+        );
+
+        assert!(holds(&condition));
+    }
+
+    #[test]
+    fn command_exists_on_path() {
+        assert!(holds(&Condition::CommandExists("sh".into())));
+        assert!(!holds(&Condition::CommandExists(
+            "ellipsis-not-a-real-command".into()
+        )));
+    }
+
+    #[test]
+    fn path_exists() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+
+        assert!(holds(&Condition::PathExists(working_dir.join("in.txt"))));
+        assert!(!holds(&Condition::PathExists(
+            working_dir.join("missing.txt")
+        )));
+    }
+}
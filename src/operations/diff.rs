@@ -0,0 +1,256 @@
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use similar::TextDiff;
+
+use crate::domain::{Operation, Step};
+use crate::operations::inspect;
+
+/// A unified diff from what is at a copy's target to its source, or
+/// nothing if they're the same
+///
+/// If the source can't be read the copy would fail, so the reason is given
+/// instead. A target that can't be read is still replaced, so all of the
+/// source is shown as new.
+pub fn copy_diff(from: &Path, to: &Path) -> Option<String> {
+    if inspect::has_same_contents(from, to) {
+        return None;
+    }
+
+    let new = match fs::read(from) {
+        Ok(new) => new,
+        Err(error) => return Some(unreadable(from, to, &error)),
+    };
+    let (old, note) = match fs::read(to) {
+        Ok(old) => (old, String::new()),
+        Err(error) if error.kind() == ErrorKind::NotFound => (Vec::new(), String::new()),
+        Err(error) => (
+            Vec::new(),
+            format!(
+                "could not read `{}`, so all of the copy is shown as new: {}\n",
+                to.display(),
+                error
+            ),
+        ),
+    };
+
+    match (String::from_utf8(old), String::from_utf8(new)) {
+        (Ok(old), Ok(new)) => Some(note + &unified(to, from, &old, &new)),
+        _ => Some(format!(
+            "{}Binary files {} and {} differ\n",
+            note,
+            to.display(),
+            from.display()
+        )),
+    }
+}
+
+/// Where a link's target points now and where it would point instead, or
+/// nothing if it already points at the source
+pub fn link_diff(from: &Path, to: &Path) -> Option<String> {
+    if inspect::is_link_to(from, to) {
+        return None;
+    }
+
+    let old = match fs::symlink_metadata(to) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            format!("{}\n", fs::read_link(to).unwrap_or_default().display())
+        }
+        Ok(metadata) if metadata.is_dir() => "(a directory)\n".into(),
+        Ok(_) => "(a file)\n".into(),
+        Err(_) => String::new(),
+    };
+
+    Some(unified(to, to, &old, &format!("{}\n", from.display())))
+}
+
+/// The differences for each copy and link, in order
+pub fn diff<'a, I>(steps: I) -> String
+where
+    I: IntoIterator<Item = &'a Step>,
+{
+    steps
+        .into_iter()
+        .filter_map(|step| match &step.operation {
+            Operation::Copy { from, to } => copy_diff(&from.location, &to.location),
+            Operation::Link { from, to, .. } => link_diff(&from.location, &to.location),
+            Operation::Exec(_) | Operation::Shell { .. } => None,
+        })
+        .collect()
+}
+
+fn unreadable(path: &Path, to: &Path, error: &io::Error) -> String {
+    format!(
+        "could not read `{}`, so the copy to `{}` would fail: {}\n",
+        path.display(),
+        to.display(),
+        error
+    )
+}
+
+fn unified(old_path: &Path, new_path: &Path, old: &str, new: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(
+            &old_path.display().to_string(),
+            &new_path.display().to_string(),
+        )
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs as unixfs;
+
+    use indoc::formatdoc;
+
+    use super::{copy_diff, link_diff};
+
+    #[test]
+    fn no_diff_when_the_copy_is_the_same() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!\n").unwrap();
+        fs::write(working_dir.join("out.txt"), "Hello, World!\n").unwrap();
+
+        assert_eq!(
+            None,
+            copy_diff(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+        );
+    }
+
+    #[test]
+    fn diff_from_the_target_to_the_source() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!\nBye\n").unwrap();
+        fs::write(working_dir.join("out.txt"), "Hello, World!\nI exist\n").unwrap();
+
+        assert_eq!(
+            Some(formatdoc! {"
+                --- {target}
+                +++ {source}
+                @@ -1,2 +1,2 @@
+                 Hello, World!
+                -I exist
+                +Bye
+                ",
+                target = working_dir.join("out.txt").display(),
+                source = working_dir.join("in.txt").display(),
+            }),
+            copy_diff(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+        );
+    }
+
+    #[test]
+    fn binary_files_only_say_they_differ() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.bin"), [0xff, 0x00]).unwrap();
+
+        assert!(
+            copy_diff(&working_dir.join("in.bin"), &working_dir.join("out.bin"))
+                .unwrap()
+                .starts_with("Binary files")
+        );
+    }
+
+    #[test]
+    fn missing_source_is_an_error_rather_than_a_diff() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("out.txt"), "I exist\n").unwrap();
+
+        assert_eq!(
+            Some(format!(
+                "could not read `{}`, so the copy to `{}` would fail: No such file or directory (os error 2)\n",
+                working_dir.join("in.txt").display(),
+                working_dir.join("out.txt").display(),
+            )),
+            copy_diff(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+        );
+    }
+
+    #[test]
+    fn unreadable_targets_are_all_new() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!\n").unwrap();
+        fs::create_dir(working_dir.join("out.txt")).unwrap();
+
+        assert_eq!(
+            Some(formatdoc! {"
+                could not read `{target}`, so all of the copy is shown as new: Is a directory (os error 21)
+                --- {target}
+                +++ {source}
+                @@ -0,0 +1 @@
+                +Hello, World!
+                ",
+                target = working_dir.join("out.txt").display(),
+                source = working_dir.join("in.txt").display(),
+            }),
+            copy_diff(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+        );
+    }
+
+    #[test]
+    fn no_diff_when_the_link_points_at_the_source() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        unixfs::symlink(working_dir.join("in.txt"), working_dir.join("out.txt")).unwrap();
+
+        assert_eq!(
+            None,
+            link_diff(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+        );
+    }
+
+    #[test]
+    fn diff_from_where_the_link_points_to_the_source() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        unixfs::symlink("/somewhere/else", working_dir.join("out.txt")).unwrap();
+
+        assert_eq!(
+            Some(formatdoc! {"
+                --- {target}
+                +++ {target}
+                @@ -1 +1 @@
+                -/somewhere/else
+                +{source}
+                ",
+                target = working_dir.join("out.txt").display(),
+                source = working_dir.join("in.txt").display(),
+            }),
+            link_diff(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+        );
+    }
+
+    #[test]
+    fn diff_for_a_link_over_a_file() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("out.txt"), "I exist").unwrap();
+
+        assert!(
+            link_diff(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+                .unwrap()
+                .contains("\n-(a file)\n")
+        );
+    }
+
+    #[test]
+    fn missing_targets_are_all_new() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!\n").unwrap();
+
+        assert_eq!(
+            Some(formatdoc! {"
+                --- {target}
+                +++ {source}
+                @@ -0,0 +1 @@
+                +Hello, World!
+                ",
+                target = working_dir.join("out.txt").display(),
+                source = working_dir.join("in.txt").display(),
+            }),
+            copy_diff(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+        );
+    }
+}
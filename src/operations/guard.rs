@@ -0,0 +1,155 @@
+use std::process::{Command, Stdio};
+
+use crate::domain::{Error, Exec};
+
+pub fn skip_reason(exec: &Exec) -> Result<Option<String>, Error> {
+    if let Some(reason) = path_skip_reason(exec) {
+        return Ok(Some(reason));
+    }
+
+    if let Some(check) = &exec.guards.unless {
+        if succeeds(exec, check)? {
+            return Ok(Some(format!("`{}` succeeded", check)));
+        }
+    }
+
+    if let Some(check) = &exec.guards.onlyif {
+        if !succeeds(exec, check)? {
+            return Ok(Some(format!("`{}` failed", check)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Why the `creates` and `removes` guards skip the command, if they do,
+/// which unlike `unless` and `onlyif` needs nothing to be run to find out
+pub fn path_skip_reason(exec: &Exec) -> Option<String> {
+    if let Some(path) = exec.guards.creates.as_ref().filter(|path| path.exists()) {
+        return Some(format!("{} exists", path.display()));
+    }
+
+    exec.guards
+        .removes
+        .as_ref()
+        .filter(|path| !path.exists())
+        .map(|path| format!("{} does not exist", path.display()))
+}
+
+fn succeeds(exec: &Exec, check: &str) -> Result<bool, Error> {
+    let mut command = Command::new(&exec.guards.shell);
+    command
+        .current_dir(&exec.working_dir)
+        .args(["-c", check])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    if exec.clear_env {
+        command.env_clear();
+    }
+
+    command
+        .envs(&exec.env)
+        .status()
+        .map(|status| status.success())
+        .map_err(|error| {
+            Error::Spawn(
+                exec.guards.shell.clone(),
+                format!("\"-c\", \"{}\"", check),
+                exec.working_dir.clone(),
+                error,
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::skip_reason;
+    use crate::domain::Exec;
+
+    fn exec(working_dir: &Path) -> Exec {
+        Exec::new(working_dir, "true".into(), vec![])
+    }
+
+    #[test]
+    fn no_guards() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+
+        assert_eq!(None, skip_reason(&exec(&working_dir)).unwrap());
+    }
+
+    #[test]
+    fn creates_a_path_that_exists() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("done.txt"), "").unwrap();
+        let mut exec = exec(&working_dir);
+        exec.guards.creates = Some(working_dir.join("done.txt"));
+
+        assert_eq!(
+            Some(format!("{} exists", working_dir.join("done.txt").display())),
+            skip_reason(&exec).unwrap()
+        );
+    }
+
+    #[test]
+    fn creates_a_path_that_is_missing() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = exec(&working_dir);
+        exec.guards.creates = Some(working_dir.join("done.txt"));
+
+        assert_eq!(None, skip_reason(&exec).unwrap());
+    }
+
+    #[test]
+    fn removes_a_path_that_is_missing() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = exec(&working_dir);
+        exec.guards.removes = Some(working_dir.join("old.txt"));
+
+        assert_eq!(
+            Some(format!(
+                "{} does not exist",
+                working_dir.join("old.txt").display()
+            )),
+            skip_reason(&exec).unwrap()
+        );
+    }
+
+    #[test]
+    fn unless_check_succeeds() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = exec(&working_dir);
+        exec.guards.unless = Some("test -d .".into());
+
+        assert_eq!(
+            Some(String::from("`test -d .` succeeded")),
+            skip_reason(&exec).unwrap()
+        );
+    }
+
+    #[test]
+    fn onlyif_check_fails() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = exec(&working_dir);
+        exec.guards.onlyif = Some("test -f missing.txt".into());
+
+        assert_eq!(
+            Some(String::from("`test -f missing.txt` failed")),
+            skip_reason(&exec).unwrap()
+        );
+    }
+
+    #[test]
+    fn checks_see_the_exec_environment() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = exec(&working_dir);
+        exec.env.insert("READY".into(), "yes".into());
+        exec.guards.onlyif = Some("test \"$READY\" = yes".into());
+
+        assert_eq!(None, skip_reason(&exec).unwrap());
+    }
+}
@@ -0,0 +1,174 @@
+use std::fs;
+use std::path::Path;
+
+use crate::operations::status::Status;
+use crate::state;
+
+pub fn is_link_to(from: &Path, to: &Path) -> bool {
+    match fs::read_link(to) {
+        Ok(target) if target == from => true,
+        Ok(_) => match (fs::canonicalize(to), fs::canonicalize(from)) {
+            (Ok(existing), Ok(expected)) => existing == expected,
+            _ => false,
+        },
+        Err(_) => false,
+    }
+}
+
+pub fn has_same_contents(from: &Path, to: &Path) -> bool {
+    if fs::symlink_metadata(to).map_or(true, |metadata| !metadata.is_file()) {
+        return false;
+    }
+
+    match (fs::read(from), fs::read(to)) {
+        (Ok(source), Ok(destination)) => source == destination,
+        _ => false,
+    }
+}
+
+/// How a copy's target compares to its source, and to the hash of what
+/// ellipsis last copied there, if it knows of one
+pub fn copy_status(from: &Path, to: &Path, copied_hash: Option<&str>) -> Status {
+    match fs::symlink_metadata(to) {
+        Err(_) => return Status::Missing,
+        Ok(metadata) if !metadata.is_file() => return Status::Conflict,
+        Ok(_) => {}
+    }
+
+    if has_same_contents(from, to) {
+        return Status::InSync;
+    }
+
+    match copied_hash {
+        Some(copied_hash) if state::hash(to).as_deref() == Some(copied_hash) => Status::OutOfDate,
+        Some(_) => Status::Modified,
+        None => Status::Conflict,
+    }
+}
+
+pub fn link_status(from: &Path, to: &Path) -> Status {
+    match fs::symlink_metadata(to) {
+        Err(_) => Status::Missing,
+        Ok(_) if is_link_to(from, to) => Status::InSync,
+        Ok(metadata) if metadata.file_type().is_symlink() => Status::WrongLink,
+        Ok(_) => Status::Conflict,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs as unixfs;
+
+    use super::{copy_status, has_same_contents, is_link_to, link_status};
+    use crate::operations::status::Status;
+    use crate::state;
+
+    #[test]
+    fn link_to_the_source() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        unixfs::symlink(working_dir.join("in.txt"), working_dir.join("out.txt")).unwrap();
+
+        assert!(is_link_to(
+            &working_dir.join("in.txt"),
+            &working_dir.join("out.txt")
+        ));
+    }
+
+    #[test]
+    fn relative_link_to_the_source() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        unixfs::symlink("in.txt", working_dir.join("out.txt")).unwrap();
+
+        assert!(is_link_to(
+            &working_dir.join("in.txt"),
+            &working_dir.join("out.txt")
+        ));
+    }
+
+    #[test]
+    fn real_file_is_not_a_link() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        fs::write(working_dir.join("out.txt"), "Hello, World!").unwrap();
+
+        assert!(!is_link_to(
+            &working_dir.join("in.txt"),
+            &working_dir.join("out.txt")
+        ));
+    }
+
+    #[test]
+    fn same_contents() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        fs::write(working_dir.join("out.txt"), "Hello, World!").unwrap();
+
+        assert!(has_same_contents(
+            &working_dir.join("in.txt"),
+            &working_dir.join("out.txt")
+        ));
+    }
+
+    #[test]
+    fn different_contents() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        fs::write(working_dir.join("out.txt"), "I exist").unwrap();
+
+        assert!(!has_same_contents(
+            &working_dir.join("in.txt"),
+            &working_dir.join("out.txt")
+        ));
+    }
+
+    #[test]
+    fn statuses_of_a_copy() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let from = working_dir.join("in.txt");
+        let to = working_dir.join("out.txt");
+        fs::write(&from, "Hello, World!").unwrap();
+
+        assert_eq!(Status::Missing, copy_status(&from, &to, None));
+
+        fs::write(&to, "Hello, World!").unwrap();
+        assert_eq!(Status::InSync, copy_status(&from, &to, None));
+
+        let copied_hash = state::hash(&to).unwrap();
+        fs::write(&from, "Hello, again!").unwrap();
+        assert_eq!(
+            Status::OutOfDate,
+            copy_status(&from, &to, Some(&copied_hash))
+        );
+
+        fs::write(&to, "Edited").unwrap();
+        assert_eq!(
+            Status::Modified,
+            copy_status(&from, &to, Some(&copied_hash))
+        );
+        assert_eq!(Status::Conflict, copy_status(&from, &to, None));
+    }
+
+    #[test]
+    fn statuses_of_a_link() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let from = working_dir.join("in.txt");
+        let to = working_dir.join("out.txt");
+        fs::write(&from, "Hello, World!").unwrap();
+
+        assert_eq!(Status::Missing, link_status(&from, &to));
+
+        unixfs::symlink(&from, &to).unwrap();
+        assert_eq!(Status::InSync, link_status(&from, &to));
+
+        fs::remove_file(&to).unwrap();
+        unixfs::symlink(working_dir.join("elsewhere.txt"), &to).unwrap();
+        assert_eq!(Status::WrongLink, link_status(&from, &to));
+
+        fs::remove_file(&to).unwrap();
+        fs::write(&to, "Hello, World!").unwrap();
+        assert_eq!(Status::Conflict, link_status(&from, &to));
+    }
+}
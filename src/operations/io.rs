@@ -1,32 +1,94 @@
 use std::fs;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::iter;
 use std::os::unix::fs as unixfs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::thread;
 
 use crate::domain;
-use crate::domain::Error;
+use crate::domain::{Error, Exec, OperationCommand, OperationOutcome};
+use crate::operations::process::Finished;
+use crate::operations::{guard, inspect, process, undo};
+use crate::state;
+use crate::state::State;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Driver {}
+pub struct Driver {
+    group_output: bool,
+    backups: Option<Backups>,
+}
+
+/// Where to keep anything a copy or link replaces, and what ellipsis has
+/// made, which isn't kept as long as it is still as ellipsis left it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Backups {
+    pub dir: PathBuf,
+    pub known: State,
+}
 
 impl Driver {
     pub const fn new() -> Self {
-        Self {}
+        Self {
+            group_output: false,
+            backups: None,
+        }
+    }
+
+    /// Hold back the output of each operation until it has finished, so
+    /// operations running at the same time don't interleave their lines
+    pub fn with_grouped_output(self) -> Self {
+        Self {
+            group_output: true,
+            ..self
+        }
+    }
+
+    /// Move anything a copy or link replaces into the backups
+    pub fn with_backups(self, backups: Backups) -> Self {
+        Self {
+            backups: Some(backups),
+            ..self
+        }
     }
 }
 
 impl domain::Driver for Driver {
-    fn copy(self, from: &Path, to: &Path) -> Result<Self, domain::Error> {
-        Self::make_containing_directory(to)
-            .map_err(Self::link_error(from.to_path_buf(), to.to_path_buf()))?;
+    fn copy(&self, from: &Path, to: &Path) -> Result<OperationOutcome, domain::Error> {
+        if inspect::has_same_contents(from, to) {
+            return Ok(OperationOutcome::Unchanged);
+        }
+
+        let copy_error = |error| domain::Error::Copy(PathBuf::from(from), PathBuf::from(to), error);
+
+        File::open(from).map_err(copy_error)?;
+        Self::make_containing_directory(to).map_err(copy_error)?;
+
+        let backup = self.back_up(to)?;
 
         fs::copy(from, to)
-            .map_err(|error| domain::Error::Copy(PathBuf::from(from), PathBuf::from(to), error))
-            .map(|_| Self::new())
+            .map_err(|error| {
+                if let Some(backup) = backup {
+                    let _ = Self::relocate(&backup, to);
+                }
+
+                copy_error(error)
+            })
+            .map(|_| OperationOutcome::Changed)
     }
 
-    fn link(self, from: &Path, to: &Path, overwrite: bool) -> Result<Self, domain::Error> {
+    fn link(
+        &self,
+        from: &Path,
+        to: &Path,
+        overwrite: bool,
+    ) -> Result<OperationOutcome, domain::Error> {
+        if inspect::is_link_to(from, to) {
+            return Ok(OperationOutcome::Unchanged);
+        }
+
         if overwrite {
+            self.back_up(to)?;
             Self::delete_real_file_if_exists(to)
                 .map_err(Self::link_error(from.to_path_buf(), to.to_path_buf()))?;
         }
@@ -36,26 +98,152 @@ impl domain::Driver for Driver {
 
         unixfs::symlink(from, to)
             .map_err(Self::link_error(from.to_path_buf(), to.to_path_buf()))
-            .map(|_| Self::new())
+            .map(|_| OperationOutcome::Changed)
     }
 
-    fn exec(self, working_dir: &Path, command: &str, args: &[String]) -> Result<Self, Error> {
-        Command::new(command)
-            .current_dir(working_dir)
-            .args(args)
-            .output()
-            .map_err(|error| {
-                domain::Error::Exec(
-                    command.into(),
-                    args.iter()
-                        .map(|arg| format!("\"{}\"", arg))
-                        .collect::<Vec<_>>()
-                        .join(", "),
-                    working_dir.into(),
-                    error,
-                )
-            })
-            .map(|_| Self::new())
+    fn exec(&self, label: &str, exec: &Exec) -> Result<OperationOutcome, Error> {
+        if guard::skip_reason(exec)?.is_some() {
+            return Ok(OperationOutcome::Skipped);
+        }
+
+        let attempts = exec.retries + 1;
+        let mut attempt = 1;
+
+        loop {
+            let error =
+                match process::run(label, exec, self.group_output).map_err(|error| match error {
+                    process::Error::Start(error) => domain::Error::Spawn(
+                        exec.command.clone(),
+                        exec.display_args(),
+                        exec.working_dir.clone(),
+                        error,
+                    ),
+                    process::Error::Stdin(path, error) => {
+                        domain::Error::Stdin(exec.command.clone(), exec.display_args(), path, error)
+                    }
+                })? {
+                    Finished::Exited { status, .. } if exec.is_success(status) => {
+                        return Ok(OperationOutcome::Changed);
+                    }
+                    Finished::Exited { status, stderr } => domain::Error::Exec(
+                        exec.command.clone(),
+                        exec.display_args(),
+                        exec.working_dir.clone(),
+                        status,
+                        stderr,
+                    ),
+                    Finished::TimedOut => domain::Error::Timeout(
+                        exec.command.clone(),
+                        exec.display_args(),
+                        exec.working_dir.clone(),
+                        exec.timeout.unwrap_or_default(),
+                        attempt,
+                    ),
+                };
+
+            if attempt >= attempts {
+                return Err(error);
+            }
+
+            let delay = exec.backoff(attempt);
+            eprintln!(
+                "[{}] attempt {} of {} failed, retrying in {}",
+                label,
+                attempt,
+                attempts,
+                humantime::format_duration(delay)
+            );
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    fn shell(
+        &self,
+        label: &str,
+        working_dir: &Path,
+        command: &OperationCommand,
+    ) -> Result<OperationOutcome, Error> {
+        self.exec(
+            label,
+            &Exec::new(
+                working_dir,
+                command.shell.clone(),
+                vec!["-c".into(), command.run.clone()],
+            ),
+        )
+    }
+
+    fn remove(&self, path: &Path) -> Result<OperationOutcome, Error> {
+        fs::remove_file(path)
+            .map_err(|error| domain::Error::Remove(path.to_path_buf(), error))
+            .map(|_| OperationOutcome::Changed)
+    }
+
+    fn restore(&self, backup: &Path, to: &Path) -> Result<OperationOutcome, Error> {
+        Self::make_containing_directory(to)
+            .and_then(|()| Self::relocate(backup, to))
+            .map_err(|error| domain::Error::Restore(backup.to_path_buf(), to.to_path_buf(), error))
+            .map(|()| OperationOutcome::Changed)
+    }
+
+    fn skip(&self, _label: &str, _kind: &str, _reason: &str) {}
+}
+
+impl Driver {
+    /// Where the backup went, if one was made
+    ///
+    /// The first backup of a path is what was there before ellipsis, so
+    /// later ones are numbered rather than replacing it.
+    fn back_up(&self, path: &Path) -> Result<Option<PathBuf>, Error> {
+        let backups = match &self.backups {
+            Some(backups) => backups,
+            None => return Ok(None),
+        };
+
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if !metadata.is_dir() => {}
+            _ => return Ok(None),
+        }
+
+        let made_by_ellipsis =
+            backups.known.entries.iter().any(|entry| {
+                entry.target.as_deref() == Some(path) && undo::is_still_ours(entry, path)
+            });
+        if made_by_ellipsis {
+            return Ok(None);
+        }
+
+        let first = state::backup_path(&backups.dir, path);
+        let backup = iter::once(first.clone())
+            .chain((1..).map(|number| {
+                let mut numbered = first.clone().into_os_string();
+                numbered.push(format!(".{}", number));
+                PathBuf::from(numbered)
+            }))
+            .find(|backup| fs::symlink_metadata(backup).is_err())
+            .expect("there is always an unused backup number");
+
+        Self::make_containing_directory(&backup)
+            .and_then(|()| Self::relocate(path, &backup))
+            .map_err(|error| domain::Error::Backup(path.to_path_buf(), backup.clone(), error))
+            .map(|()| Some(backup))
+    }
+
+    /// Rename, or copy and remove when that would cross file systems
+    fn relocate(from: &Path, to: &Path) -> Result<(), std::io::Error> {
+        match fs::rename(from, to) {
+            Err(error) if error.kind() == ErrorKind::CrossesDevices => {
+                if fs::symlink_metadata(from)?.is_symlink() {
+                    unixfs::symlink(fs::read_link(from)?, to)?;
+                } else {
+                    fs::copy(from, to)?;
+                }
+
+                fs::remove_file(from)
+            }
+            result => result,
+        }
     }
 }
 
@@ -67,11 +255,12 @@ impl Driver {
 
 impl Driver {
     fn delete_real_file_if_exists(path: &Path) -> Result<(), std::io::Error> {
-        if !path.exists() {
-            return Ok(());
-        }
+        let file_type = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata.file_type(),
+            Err(_) => return Ok(()),
+        };
 
-        if fs::metadata(path)?.file_type().is_file() {
+        if file_type.is_file() || file_type.is_symlink() {
             fs::remove_file(path).map(|_| ())
         } else {
             Ok(())
@@ -79,30 +268,256 @@ impl Driver {
     }
 }
 
+impl Driver {
+    fn make_containing_directory(to: &Path) -> Result<(), std::io::Error> {
+        if let Some(path) = to.parent().filter(|x| !x.exists()) {
+            fs::create_dir_all(path)?;
+        };
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
     use std::fs::File;
     use std::io::{Read, Write};
-    use std::path::Path;
-
-    use super::Driver as IoDriver;
-    use crate::domain::Driver;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant, SystemTime};
+
+    use super::{Backups, Driver as IoDriver};
+    use crate::domain::{
+        Driver, Error, Exec, Operation, OperationCommand, OperationOutcome, OperationPath, Output,
+        Stdin, Step,
+    };
+    use crate::state;
+    use crate::state::{Entry, State};
+
+    fn bash(working_dir: &Path, script: &str, allowed_exit_codes: Vec<i32>) -> Exec {
+        Exec {
+            allowed_exit_codes,
+            ..Exec::new(working_dir, "bash".into(), vec!["-c".into(), script.into()])
+        }
+    }
 
     #[test]
     fn exec_file() {
         let working_dir = tempfile::tempdir().unwrap().into_path();
         IoDriver::new()
             .exec(
+                "1",
+                &bash(&working_dir, "echo Hello, World! > out.txt", vec![0]),
+            )
+            .unwrap();
+        let output_file_contents = read_file(&working_dir.join("out.txt"));
+        assert_eq!(String::from("Hello, World!\n"), output_file_contents);
+    }
+
+    #[test]
+    fn shell_script() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        IoDriver::new()
+            .shell(
+                "1",
                 &working_dir,
-                "bash",
-                &["-c".into(), "echo Hello, World! > out.txt".into()],
+                &OperationCommand {
+                    run: "GREETING=Hello\necho $GREETING, World! > out.txt\n".into(),
+                    shell: "bash".into(),
+                    undo: None,
+                },
             )
             .unwrap();
         let output_file_contents = read_file(&working_dir.join("out.txt"));
         assert_eq!(String::from("Hello, World!\n"), output_file_contents);
     }
 
+    #[test]
+    fn shell_script_that_fails() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+
+        assert!(IoDriver::new()
+            .shell(
+                "1",
+                &working_dir,
+                &OperationCommand {
+                    run: "exit 1".into(),
+                    shell: "sh".into(),
+                    undo: None,
+                },
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn exec_with_environment() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = bash(&working_dir, "echo $GREETING, $HOME! > out.txt", vec![0]);
+        exec.env.insert("GREETING".into(), "Hello".into());
+        exec.clear_env = true;
+
+        IoDriver::new().exec("1", &exec).unwrap();
+
+        let output_file_contents = read_file(&working_dir.join("out.txt"));
+        assert_eq!(String::from("Hello, !\n"), output_file_contents);
+    }
+
+    #[test]
+    fn exec_with_literal_stdin() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = bash(&working_dir, "cat > out.txt", vec![0]);
+        exec.stdin = Some(Stdin::Literal("Hello, World!".into()));
+
+        IoDriver::new().exec("1", &exec).unwrap();
+
+        let output_file_contents = read_file(&working_dir.join("out.txt"));
+        assert_eq!(String::from("Hello, World!"), output_file_contents);
+    }
+
+    #[test]
+    fn exec_with_stdin_from_file() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        write_file(&working_dir.join("in.txt"), "Hello, World!");
+        let mut exec = bash(&working_dir, "cat > out.txt", vec![0]);
+        exec.stdin = Some(Stdin::File(working_dir.join("in.txt")));
+
+        IoDriver::new().exec("1", &exec).unwrap();
+
+        let output_file_contents = read_file(&working_dir.join("out.txt"));
+        assert_eq!(String::from("Hello, World!"), output_file_contents);
+    }
+
+    #[test]
+    fn exec_that_is_guarded() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = bash(&working_dir, "echo Hello, World! > out.txt", vec![0]);
+        exec.guards.unless = Some("true".into());
+
+        let outcome = IoDriver::new().exec("1", &exec).unwrap();
+
+        assert_eq!(OperationOutcome::Skipped, outcome);
+        assert!(!working_dir.join("out.txt").exists());
+    }
+
+    #[test]
+    fn exec_that_fails() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let error = IoDriver::new()
+            .exec("1", &bash(&working_dir, "echo Oh no >&2; exit 3", vec![0]))
+            .unwrap_err();
+
+        match error {
+            Error::Exec(_, _, _, status, stderr) => {
+                assert_eq!(Some(3), status.code());
+                assert_eq!("Oh no", stderr);
+            }
+            _ => panic!("expected an exec error, got {:?}", error),
+        }
+    }
+
+    #[test]
+    fn exec_that_fails_with_an_allowed_exit_code() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let outcome = IoDriver::new()
+            .exec("1", &bash(&working_dir, "exit 3", vec![0, 3]))
+            .unwrap();
+
+        assert_eq!(OperationOutcome::Changed, outcome);
+    }
+
+    #[test]
+    fn exec_that_fails_while_capturing_output() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = bash(
+            &working_dir,
+            "echo Working; echo Oh no >&2; exit 3",
+            vec![0],
+        );
+        exec.output = Output::Capture;
+
+        let error = IoDriver::new().exec("1", &exec).unwrap_err();
+
+        match error {
+            Error::Exec(_, _, _, status, stderr) => {
+                assert_eq!(Some(3), status.code());
+                assert_eq!("Oh no", stderr);
+            }
+            _ => panic!("expected an exec error, got {:?}", error),
+        }
+    }
+
+    #[test]
+    fn exec_that_times_out() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = bash(&working_dir, "sleep 10", vec![0]);
+        exec.timeout = Some(Duration::from_millis(100));
+        exec.retries = 1;
+        exec.retry_delay = Duration::from_millis(1);
+
+        let started = Instant::now();
+        let error = IoDriver::new().exec("1", &exec).unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        match error {
+            Error::Timeout(_, _, _, timeout, attempts) => {
+                assert_eq!(Duration::from_millis(100), timeout);
+                assert_eq!(2, attempts);
+            }
+            _ => panic!("expected a timeout error, got {:?}", error),
+        }
+    }
+
+    #[test]
+    fn exec_that_succeeds_on_retry() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = bash(
+            &working_dir,
+            "if [ -f tried ]; then exit 0; else touch tried; exit 1; fi",
+            vec![0],
+        );
+        exec.retries = 2;
+        exec.retry_delay = Duration::from_millis(1);
+
+        let outcome = IoDriver::new().exec("1", &exec).unwrap();
+
+        assert_eq!(OperationOutcome::Changed, outcome);
+    }
+
+    #[test]
+    fn exec_that_fails_every_retry() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = bash(&working_dir, "echo try >> tries.txt; exit 1", vec![0]);
+        exec.retries = 2;
+        exec.retry_delay = Duration::from_millis(1);
+
+        assert!(IoDriver::new().exec("1", &exec).is_err());
+        assert_eq!("try\ntry\ntry\n", read_file(&working_dir.join("tries.txt")));
+    }
+
+    #[test]
+    fn exec_that_cannot_start() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = bash(&working_dir, "true", vec![0]);
+        exec.command = "this-command-does-not-exist".into();
+
+        assert!(matches!(
+            IoDriver::new().exec("1", &exec),
+            Err(Error::Spawn(_, _, _, _))
+        ));
+    }
+
+    #[test]
+    fn exec_with_missing_stdin_file() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut exec = bash(&working_dir, "cat > out.txt", vec![0]);
+        exec.stdin = Some(Stdin::File(working_dir.join("in.txt")));
+
+        assert!(matches!(
+            IoDriver::new().exec("1", &exec),
+            Err(Error::Stdin(_, _, path, _)) if path == working_dir.join("in.txt")
+        ));
+    }
+
     #[test]
     fn copy_file() {
         let working_dir = tempfile::tempdir().unwrap().into_path();
@@ -115,6 +530,19 @@ mod tests {
         assert_eq!(String::from("Hello, World!"), output_file_contents);
     }
 
+    #[test]
+    fn copy_file_that_is_already_copied() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        write_file(&working_dir.join("in.txt"), "Hello, World!");
+        write_file(&working_dir.join("out.txt"), "Hello, World!");
+
+        let outcome = IoDriver::new()
+            .copy(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+            .unwrap();
+
+        assert_eq!(OperationOutcome::Unchanged, outcome);
+    }
+
     #[test]
     fn copy_file_into_deep_dir() {
         let working_dir = tempfile::tempdir().unwrap().into_path();
@@ -221,6 +649,241 @@ mod tests {
             .is_symlink());
     }
 
+    /// Backups beside the working directory, knowing ellipsis copied
+    /// `in.txt` to each of `owned` as they are now
+    fn backups(working_dir: &Path, owned: &[&str]) -> Backups {
+        Backups {
+            dir: working_dir.join("backups"),
+            known: State {
+                entries: owned
+                    .iter()
+                    .map(|name| {
+                        Entry::new(
+                            &Step::new(
+                                name,
+                                Operation::Copy {
+                                    from: OperationPath::new(working_dir, working_dir, "in.txt"),
+                                    to: OperationPath::new(working_dir, working_dir, name),
+                                },
+                            ),
+                            SystemTime::now(),
+                        )
+                    })
+                    .collect(),
+                ..State::default()
+            },
+        }
+    }
+
+    #[test]
+    fn link_file_and_overwrite_keeping_a_backup() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        write_file(&working_dir.join("in.txt"), "Hello, World!");
+        write_file(&working_dir.join("out.txt"), "I exist");
+
+        IoDriver::new()
+            .with_backups(backups(&working_dir, &[]))
+            .link(
+                &working_dir.join("in.txt"),
+                &working_dir.join("out.txt"),
+                true,
+            )
+            .unwrap();
+
+        assert_eq!(
+            String::from("I exist"),
+            read_file(&state::backup_path(
+                &working_dir.join("backups"),
+                &working_dir.join("out.txt")
+            ))
+        );
+    }
+
+    #[test]
+    fn copy_file_over_another_keeping_a_backup() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        write_file(&working_dir.join("in.txt"), "Hello, World!");
+        write_file(&working_dir.join("out.txt"), "I exist");
+        let backup = state::backup_path(&working_dir.join("backups"), &working_dir.join("out.txt"));
+        let driver = IoDriver::new().with_backups(backups(&working_dir, &[]));
+
+        driver
+            .copy(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+            .unwrap();
+        write_file(&working_dir.join("in.txt"), "Hello, again!");
+        driver
+            .copy(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+            .unwrap();
+
+        assert_eq!(String::from("I exist"), read_file(&backup));
+    }
+
+    #[test]
+    fn copy_over_an_edited_copy_keeps_a_backup() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        write_file(&working_dir.join("in.txt"), "Hello, World!");
+        write_file(&working_dir.join("out.txt"), "Hello, World!");
+        let driver = IoDriver::new().with_backups(backups(&working_dir, &["out.txt"]));
+        write_file(&working_dir.join("out.txt"), "Edited");
+        write_file(&working_dir.join("in.txt"), "Hello, again!");
+
+        driver
+            .copy(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+            .unwrap();
+
+        assert_eq!(
+            String::from("Edited"),
+            read_file(&state::backup_path(
+                &working_dir.join("backups"),
+                &working_dir.join("out.txt")
+            ))
+        );
+    }
+
+    #[test]
+    fn overwriting_a_file_that_replaced_a_link_keeps_a_backup() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        write_file(&working_dir.join("in.txt"), "Hello, World!");
+        let driver = IoDriver::new().with_backups(backups(&working_dir, &[]));
+        driver
+            .link(
+                &working_dir.join("in.txt"),
+                &working_dir.join("out.txt"),
+                true,
+            )
+            .unwrap();
+        let known = State {
+            entries: vec![Entry::new(
+                &Step::new(
+                    "dotfile",
+                    Operation::Link {
+                        from: OperationPath::new(&working_dir, &working_dir, "in.txt"),
+                        to: OperationPath::new(&working_dir, &working_dir, "out.txt"),
+                        overwrite: true,
+                    },
+                ),
+                SystemTime::now(),
+            )],
+            ..State::default()
+        };
+        fs::remove_file(working_dir.join("out.txt")).unwrap();
+        write_file(&working_dir.join("out.txt"), "Mine now");
+
+        IoDriver::new()
+            .with_backups(Backups {
+                known,
+                ..backups(&working_dir, &[])
+            })
+            .link(
+                &working_dir.join("in.txt"),
+                &working_dir.join("out.txt"),
+                true,
+            )
+            .unwrap();
+
+        assert_eq!(
+            String::from("Mine now"),
+            read_file(&state::backup_path(
+                &working_dir.join("backups"),
+                &working_dir.join("out.txt")
+            ))
+        );
+    }
+
+    #[test]
+    fn later_backups_are_numbered_so_the_first_is_kept() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let backup = state::backup_path(&working_dir.join("backups"), &working_dir.join("out.txt"));
+        write_file(&working_dir.join("in.txt"), "Hello, World!");
+        write_file(&working_dir.join("out.txt"), "Original");
+        let driver = IoDriver::new().with_backups(backups(&working_dir, &[]));
+        driver
+            .copy(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+            .unwrap();
+        write_file(&working_dir.join("out.txt"), "Edited");
+
+        driver
+            .copy(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+            .unwrap();
+
+        assert_eq!(String::from("Original"), read_file(&backup));
+        assert_eq!(
+            String::from("Edited"),
+            read_file(&PathBuf::from(format!("{}.1", backup.display())))
+        );
+    }
+
+    #[test]
+    fn copy_missing_file_leaves_the_target_in_place() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        write_file(&working_dir.join("out.txt"), "I exist");
+
+        let result = IoDriver::new()
+            .with_backups(backups(&working_dir, &[]))
+            .copy(&working_dir.join("in.txt"), &working_dir.join("out.txt"));
+
+        assert!(matches!(result, Err(Error::Copy(..))));
+        assert_eq!(
+            String::from("I exist"),
+            read_file(&working_dir.join("out.txt"))
+        );
+        assert!(!working_dir.join("backups").exists());
+    }
+
+    #[test]
+    fn copy_that_fails_puts_the_backup_back() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::create_dir(working_dir.join("in")).unwrap();
+        write_file(&working_dir.join("out.txt"), "I exist");
+
+        let result = IoDriver::new()
+            .with_backups(backups(&working_dir, &[]))
+            .copy(&working_dir.join("in"), &working_dir.join("out.txt"));
+
+        assert!(matches!(result, Err(Error::Copy(..))));
+        assert_eq!(
+            String::from("I exist"),
+            read_file(&working_dir.join("out.txt"))
+        );
+        assert!(
+            !state::backup_path(&working_dir.join("backups"), &working_dir.join("out.txt"))
+                .exists()
+        );
+    }
+
+    #[test]
+    fn copy_file_over_its_own_copy_without_a_backup() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        write_file(&working_dir.join("in.txt"), "Hello, World!");
+        write_file(&working_dir.join("out.txt"), "An old copy");
+
+        IoDriver::new()
+            .with_backups(backups(&working_dir, &["out.txt"]))
+            .copy(&working_dir.join("in.txt"), &working_dir.join("out.txt"))
+            .unwrap();
+
+        assert!(!working_dir.join("backups").exists());
+    }
+
+    #[test]
+    fn restore_a_backup() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        write_file(&working_dir.join("backup.txt"), "I exist");
+
+        IoDriver::new()
+            .restore(
+                &working_dir.join("backup.txt"),
+                &working_dir.join("deep").join("out.txt"),
+            )
+            .unwrap();
+
+        assert_eq!(
+            String::from("I exist"),
+            read_file(&working_dir.join("deep").join("out.txt"))
+        );
+        assert!(!working_dir.join("backup.txt").exists());
+    }
+
     #[test]
     fn link_file_and_do_not_overwrite() {
         let working_dir = tempfile::tempdir().unwrap().into_path();
@@ -236,6 +899,54 @@ mod tests {
             .is_err());
     }
 
+    #[test]
+    fn link_file_that_is_already_linked() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        write_file(&working_dir.join("in.txt"), "Hello, World!");
+
+        let driver = IoDriver::new();
+        let first = driver
+            .link(
+                &working_dir.join("in.txt"),
+                &working_dir.join("out.txt"),
+                false,
+            )
+            .unwrap();
+        let second = driver
+            .link(
+                &working_dir.join("in.txt"),
+                &working_dir.join("out.txt"),
+                false,
+            )
+            .unwrap();
+
+        assert_eq!(OperationOutcome::Changed, first);
+        assert_eq!(OperationOutcome::Unchanged, second);
+    }
+
+    #[test]
+    fn link_file_and_overwrite_link_to_elsewhere() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        write_file(&working_dir.join("in.txt"), "Hello, World!");
+        write_file(&working_dir.join("other.txt"), "Somewhere else");
+        std::os::unix::fs::symlink(working_dir.join("other.txt"), working_dir.join("out.txt"))
+            .unwrap();
+
+        let outcome = IoDriver::new()
+            .link(
+                &working_dir.join("in.txt"),
+                &working_dir.join("out.txt"),
+                true,
+            )
+            .unwrap();
+
+        assert_eq!(OperationOutcome::Changed, outcome);
+        assert_eq!(
+            working_dir.join("in.txt"),
+            fs::read_link(working_dir.join("out.txt")).unwrap()
+        );
+    }
+
     fn read_file(working_dir: &Path) -> String {
         let mut output_file_contents = String::new();
         File::open(working_dir)
@@ -246,17 +957,7 @@ mod tests {
     }
 
     fn write_file(working_dir: &Path, text: &str) {
-        let mut file = File::create(&working_dir).unwrap();
+        let mut file = File::create(working_dir).unwrap();
         write!(file, "{}", text).unwrap();
     }
 }
-
-impl Driver {
-    fn make_containing_directory(to: &Path) -> Result<(), std::io::Error> {
-        if let Some(path) = to.parent().filter(|x| !x.exists()) {
-            fs::create_dir_all(path)?;
-        };
-
-        Ok(())
-    }
-}
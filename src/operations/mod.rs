@@ -1,7 +1,17 @@
 mod blackhole;
+mod condition;
+mod diff;
+mod guard;
+mod inspect;
 mod io;
+mod process;
 mod runner;
+mod status;
+mod undo;
 
 pub use blackhole::Driver as BlackholeDriver;
-pub use io::Driver as IoDriver;
-pub use runner::{run, Error as RunnerError};
+pub use diff::diff;
+pub use io::{Backups, Driver as IoDriver};
+pub use runner::{run, Report};
+pub use status::{status, table};
+pub use undo::{dangling, prune_after, targets, uninstall};
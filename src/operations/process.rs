@@ -0,0 +1,220 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::sync::mpsc::{RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::domain::{Exec, Output, Stdin};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub enum Finished {
+    Exited { status: ExitStatus, stderr: String },
+    TimedOut,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The command couldn't be started, or its output couldn't be handled
+    Start(io::Error),
+    /// The file to give the command on stdin couldn't be opened
+    Stdin(PathBuf, io::Error),
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Start(error)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+pub fn run(label: &str, exec: &Exec, group_output: bool) -> Result<Finished, Error> {
+    let mut command = Command::new(&exec.command);
+    command
+        .current_dir(&exec.working_dir)
+        .args(&exec.args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    if exec.clear_env {
+        command.env_clear();
+    }
+
+    command.envs(&exec.env);
+
+    match &exec.stdin {
+        None => command.stdin(Stdio::null()),
+        Some(Stdin::Literal(_)) => command.stdin(Stdio::piped()),
+        Some(Stdin::File(path)) => {
+            command.stdin(File::open(path).map_err(|error| Error::Stdin(path.clone(), error))?)
+        }
+    };
+
+    let mut child = command.spawn()?;
+
+    let writer = match (&exec.stdin, child.stdin.take()) {
+        (Some(Stdin::Literal(input)), Some(mut stdin)) => {
+            let input = input.clone();
+            Some(thread::spawn(move || stdin.write_all(input.as_bytes())))
+        }
+        _ => None,
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let readers = vec![
+        child
+            .stdout
+            .take()
+            .map(|stdout| read_lines(Stream::Stdout, stdout, sender.clone())),
+        child
+            .stderr
+            .take()
+            .map(|stderr| read_lines(Stream::Stderr, stderr, sender.clone())),
+    ];
+    drop(sender);
+
+    let deadline = exec.timeout.map(|timeout| Instant::now() + timeout);
+    let stream_live = exec.output == Output::Stream && !group_output;
+    let mut captured = Vec::new();
+    loop {
+        let received = match deadline {
+            Some(deadline) => {
+                receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match received {
+            Ok(received) => {
+                captured.push(received);
+
+                if stream_live {
+                    if let Err(error) = print_lines(label, &captured[captured.len() - 1..]) {
+                        kill(&mut child)?;
+                        return Err(error.into());
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {
+                let printed = if exec.output == Output::Stream && group_output {
+                    print_lines(label, &captured)
+                } else {
+                    Ok(())
+                };
+                let killed = kill(&mut child);
+                printed?;
+
+                return Ok(killed?);
+            }
+        }
+    }
+
+    for reader in readers.into_iter().flatten() {
+        reader.join().expect("output reader panicked");
+    }
+
+    if let Some(writer) = writer {
+        match writer.join().expect("input writer panicked") {
+            Err(error) if error.kind() != ErrorKind::BrokenPipe => return Err(error.into()),
+            _ => {}
+        }
+    }
+
+    let status = match wait(&mut child, deadline)? {
+        Some(status) => status,
+        None => return Ok(kill(&mut child)?),
+    };
+
+    let show_output = match exec.output {
+        Output::Stream => group_output,
+        Output::Capture => !exec.is_success(status),
+    };
+    if show_output {
+        print_lines(label, &captured)?;
+    }
+
+    Ok(Finished::Exited {
+        status,
+        stderr: captured
+            .into_iter()
+            .filter(|(stream, _)| *stream == Stream::Stderr)
+            .map(|(_, line)| line)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    })
+}
+
+fn wait(child: &mut Child, deadline: Option<Instant>) -> Result<Option<ExitStatus>, io::Error> {
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => return child.wait().map(Some),
+    };
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn kill(child: &mut Child) -> Result<Finished, io::Error> {
+    child.kill()?;
+    child.wait()?;
+
+    Ok(Finished::TimedOut)
+}
+
+fn read_lines<R>(
+    stream: Stream,
+    source: R,
+    sender: Sender<(Stream, String)>,
+) -> thread::JoinHandle<()>
+where
+    R: Read + Send + 'static,
+{
+    thread::spawn(move || {
+        for line in BufReader::new(source).split(b'\n').map_while(Result::ok) {
+            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+
+            if sender.send((stream, line)).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Print each line with its label, stopping quietly if whatever is reading
+/// our output has gone away
+fn print_lines(label: &str, lines: &[(Stream, String)]) -> Result<(), io::Error> {
+    let mut stdout = io::stdout().lock();
+    let mut stderr = io::stderr().lock();
+
+    for (stream, line) in lines {
+        match stream {
+            Stream::Stdout => writeln!(stdout, "[{}] {}", label, line),
+            Stream::Stderr => writeln!(stderr, "[{}] {}", label, line),
+        }
+        .or_else(|error| match error.kind() {
+            ErrorKind::BrokenPipe => Ok(()),
+            _ => Err(error),
+        })?;
+    }
+
+    Ok(())
+}
@@ -1,68 +1,366 @@
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter};
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc;
+use std::thread;
+
 use thiserror::Error as ThisError;
 
 use crate::config::Config;
 use crate::domain;
-use crate::domain::{Driver, Operation};
+use crate::domain::{Driver, Operation, OperationOutcome, Origin, Step};
+use crate::operations::condition;
+use crate::state::Store;
+
+pub fn run<T>(input: Config, driver: &T, state: Option<&Store>) -> Report
+where
+    T: Driver,
+{
+    let Config {
+        keep_going,
+        jobs,
+        selection,
+        steps,
+        ..
+    } = input;
+    let steps = selection.select(steps).collect::<Vec<_>>();
+    let positions = steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| (step.name.as_str(), index))
+        .collect::<BTreeMap<_, _>>();
+    let mut progress = steps.iter().map(|_| Progress::Waiting).collect::<Vec<_>>();
+    let mut failed = BTreeSet::new();
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        let mut running = 0;
+        let mut stopping = false;
+
+        loop {
+            for (index, step) in steps.iter().enumerate() {
+                if stopping || running >= jobs {
+                    break;
+                }
+
+                let waiting_on_needs = step.needs.iter().any(|need| {
+                    positions
+                        .get(need.as_str())
+                        .is_some_and(|position| !matches!(progress[*position], Progress::Done(_)))
+                });
+                if !matches!(progress[index], Progress::Waiting) || waiting_on_needs {
+                    continue;
+                }
+
+                if let Some(need) = step.needs.iter().find(|need| failed.contains(*need)) {
+                    driver.skip(
+                        &step.name,
+                        step.operation.kind(),
+                        &format!("needs `{}` which failed", need),
+                    );
+                    progress[index] = Progress::Done(Ok(OperationOutcome::Skipped));
+                    failed.insert(step.name.clone());
+                    continue;
+                }
+
+                progress[index] = Progress::Running;
+                running += 1;
+
+                let sender = sender.clone();
+                scope.spawn(move || {
+                    let result =
+                        panic::catch_unwind(AssertUnwindSafe(|| perform(driver, state, step)))
+                            .unwrap_or_else(|cause| {
+                                Err(domain::Error::Panic(panic_message(&*cause)))
+                            });
+
+                    sender
+                        .send((index, result))
+                        .expect("runner stopped listening for results");
+                });
+            }
+
+            if running == 0 {
+                break;
+            }
+
+            let (index, result) = receiver
+                .recv()
+                .expect("every running operation sends its result");
+            running -= 1;
+
+            if result.is_err() && !steps[index].ignore_errors {
+                failed.insert(steps[index].name.clone());
+                stopping = stopping || !keep_going;
+            }
+
+            progress[index] = Progress::Done(result);
+        }
+    });
+
+    let mut report = Report::default();
+
+    for (step, progress) in steps.into_iter().zip(progress) {
+        match progress {
+            Progress::Done(Ok(outcome)) => report.summary.record(outcome),
+            Progress::Done(Err(error)) => {
+                report.fail(step.name, step.origin, error, step.ignore_errors);
+            }
+            Progress::Waiting | Progress::Running => {}
+        }
+    }
+
+    report
+}
+
+fn panic_message(cause: &(dyn Any + Send)) -> String {
+    cause
+        .downcast_ref::<&str>()
+        .map(|message| (*message).to_string())
+        .or_else(|| cause.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "an unknown cause".into())
+}
+
+enum Progress {
+    Waiting,
+    Running,
+    Done(Result<OperationOutcome, domain::Error>),
+}
+
+fn perform<T>(
+    driver: &T,
+    state: Option<&Store>,
+    step: &Step,
+) -> Result<OperationOutcome, domain::Error>
+where
+    T: Driver,
+{
+    let outcome = operate(driver, step)?;
+
+    if let Some(state) = state {
+        state.record(step, outcome).map_err(domain::Error::State)?;
+    }
+
+    Ok(outcome)
+}
 
-pub fn run<T>(input: Config, driver: T) -> Result<T, Error>
+fn operate<T>(driver: &T, step: &Step) -> Result<OperationOutcome, domain::Error>
 where
     T: Driver,
 {
-    input
-        .operations
-        .into_iter()
-        .try_fold(driver, |driver, operation| match operation {
-            Operation::Copy { from, to } => driver.copy(&from.location, &to.location),
-            Operation::Link {
-                from,
-                to,
-                overwrite,
-            } => driver.link(&from.location, &to.location, overwrite),
-            Operation::Exec {
-                working_dir,
-                command,
-                args,
-            } => driver.exec(&working_dir, &command, &args),
-        })
-        .map_err(Error::from)
+    let label = &step.name;
+
+    if let Some(when) = &step.when {
+        if !condition::holds(&when.condition) {
+            driver.skip(
+                label,
+                step.operation.kind(),
+                &format!("when `{}` is false", when.expression),
+            );
+            return Ok(OperationOutcome::Skipped);
+        }
+    }
+
+    match &step.operation {
+        Operation::Copy { from, to } => driver.copy(&from.location, &to.location),
+        Operation::Link {
+            from,
+            to,
+            overwrite,
+        } => driver.link(&from.location, &to.location, *overwrite),
+        Operation::Exec(exec) => driver.exec(label, exec),
+        Operation::Shell {
+            working_dir,
+            command,
+        } => driver.shell(label, working_dir, command),
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub summary: Summary,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    pub(super) fn fail(
+        &mut self,
+        label: String,
+        origin: Option<Origin>,
+        error: domain::Error,
+        ignored: bool,
+    ) {
+        if ignored {
+            self.summary.ignored += 1;
+        } else {
+            self.summary.failed += 1;
+        }
+
+        self.failures.push(Failure {
+            label,
+            origin,
+            error,
+            ignored,
+        });
+    }
+
+    pub fn merge(&mut self, other: Self) {
+        self.summary.ok += other.summary.ok;
+        self.summary.changed += other.summary.changed;
+        self.summary.skipped += other.summary.skipped;
+        self.summary.failed += other.summary.failed;
+        self.summary.ignored += other.summary.ignored;
+        self.failures.extend(other.failures);
+    }
+
+    pub const fn result(&self) -> Result<(), Error> {
+        if self.summary.failed == 0 {
+            Ok(())
+        } else {
+            Err(Error::Failed(self.summary.failed))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Failure {
+    pub label: String,
+    pub origin: Option<Origin>,
+    pub error: domain::Error,
+    pub ignored: bool,
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] ", self.label)?;
+
+        if self.ignored {
+            write!(f, "(ignored) ")?;
+        }
+
+        write!(f, "{}", self.error)?;
+
+        if let Some(origin) = &self.origin {
+            write!(f, "\n    in {}", origin)?;
+        }
+
+        let mut source = self.error.source();
+        while let Some(cause) = source {
+            write!(f, "\n    caused by: {}", cause)?;
+            source = cause.source();
+        }
+
+        if let Some(hint) = self.error.hint() {
+            write!(f, "\n    hint: {}", hint)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Summary {
+    pub ok: usize,
+    pub changed: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub ignored: usize,
+}
+
+impl Summary {
+    pub(super) fn record(&mut self, outcome: OperationOutcome) {
+        match outcome {
+            OperationOutcome::Changed => {
+                self.ok += 1;
+                self.changed += 1;
+            }
+            OperationOutcome::Unchanged => self.ok += 1,
+            OperationOutcome::Skipped => self.skipped += 1,
+        }
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ok, {} changed, {} skipped, {} failed, {} ignored",
+            self.ok, self.changed, self.skipped, self.failed, self.ignored
+        )
+    }
 }
 
 #[derive(ThisError, Debug)]
 pub enum Error {
-    #[error("io error")]
-    Io(#[from] std::io::Error),
-    #[error("driver error")]
-    Driver(#[from] domain::Error),
+    #[error("{0} operation(s) failed")]
+    Failed(usize),
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::path::Path;
+
     use crate::config::Config;
     use crate::domain;
-    use crate::domain::{DriverTypes, OperationPath};
-    use crate::operations::runner::run;
-    use crate::operations::BlackholeDriver;
+    use crate::domain::{
+        Condition, Driver, DriverTypes, Exec, Operation, OperationCommand, OperationOutcome,
+        OperationPath, Origin, Position, Selection, Step, When,
+    };
+    use crate::operations::runner::{run, Summary};
+    use crate::operations::{BlackholeDriver, IoDriver};
+    use crate::state::Store;
+
+    fn config(operations: Vec<Operation>) -> Config {
+        Config {
+            driver: DriverTypes::Blackhole,
+            keep_going: false,
+            jobs: 1,
+            selection: Selection::default(),
+            steps: operations
+                .into_iter()
+                .enumerate()
+                .map(|(index, operation)| Step::new(&(index + 1).to_string(), operation))
+                .collect(),
+        }
+    }
+
+    fn missing_copy(working_dir: &Path) -> Operation {
+        Operation::Copy {
+            from: OperationPath::new(working_dir, working_dir, "missing.txt"),
+            to: OperationPath::new(working_dir, working_dir, "out.txt"),
+        }
+    }
+
+    fn copy(working_dir: &Path) -> Operation {
+        Operation::Copy {
+            from: OperationPath::new(working_dir, working_dir, "in.txt"),
+            to: OperationPath::new(working_dir, working_dir, "copy.txt"),
+        }
+    }
 
     #[test]
     fn copy_file() {
         let working_dir = tempfile::tempdir().unwrap().into_path();
-        let input = Config {
-            driver: DriverTypes::Blackhole,
-            operations: vec![domain::Operation::Copy {
-                to: OperationPath::new(
-                    &working_dir,
-                    &tempfile::tempdir().unwrap().into_path(),
-                    "out.txt",
-                ),
-                from: OperationPath::new(
-                    &working_dir,
-                    &tempfile::tempdir().unwrap().into_path(),
-                    "in.txt",
-                ),
-            }],
-        };
+        let input = config(vec![domain::Operation::Copy {
+            to: OperationPath::new(
+                &working_dir,
+                &tempfile::tempdir().unwrap().into_path(),
+                "out.txt",
+            ),
+            from: OperationPath::new(
+                &working_dir,
+                &tempfile::tempdir().unwrap().into_path(),
+                "in.txt",
+            ),
+        }]);
 
-        let driver = run(input, BlackholeDriver::new()).unwrap();
+        let driver = BlackholeDriver::new();
+        run(input, &driver, None);
 
         assert_eq!(
             vec![(
@@ -73,30 +371,28 @@ mod tests {
                     working_dir.join("out.txt")
                 )
             )],
-            driver.log
+            driver.into_log()
         );
     }
 
     #[test]
     fn exec_file() {
         let working_dir = tempfile::tempdir().unwrap().into_path();
-        let input = Config {
-            driver: DriverTypes::Blackhole,
-            operations: vec![domain::Operation::Exec {
-                working_dir: working_dir.clone(),
-                command: "bash".into(),
-                args: vec!["Hello".into()],
-            }],
-        };
+        let input = config(vec![domain::Operation::Exec(Box::new(domain::Exec::new(
+            &working_dir,
+            "bash".into(),
+            vec!["Hello".into()],
+        )))]);
 
-        let driver = run(input, BlackholeDriver::new()).unwrap();
+        let driver = BlackholeDriver::new();
+        run(input, &driver, None);
 
         assert_eq!(
             vec![(
                 String::from("exec"),
                 format!("in {:?} \"bash\" [\"Hello\"]", working_dir,)
             )],
-            driver.log
+            driver.into_log()
         );
     }
 
@@ -104,24 +400,22 @@ mod tests {
     fn link_file() {
         let working_dir = tempfile::tempdir().unwrap().into_path();
 
-        let input = Config {
-            driver: DriverTypes::Blackhole,
-            operations: vec![domain::Operation::Link {
-                to: OperationPath::new(
-                    &working_dir,
-                    &tempfile::tempdir().unwrap().into_path(),
-                    "out.txt",
-                ),
-                from: OperationPath::new(
-                    &working_dir,
-                    &tempfile::tempdir().unwrap().into_path(),
-                    "in.txt",
-                ),
-                overwrite: false,
-            }],
-        };
+        let input = config(vec![domain::Operation::Link {
+            to: OperationPath::new(
+                &working_dir,
+                &tempfile::tempdir().unwrap().into_path(),
+                "out.txt",
+            ),
+            from: OperationPath::new(
+                &working_dir,
+                &tempfile::tempdir().unwrap().into_path(),
+                "in.txt",
+            ),
+            overwrite: false,
+        }]);
 
-        let driver = run(input, BlackholeDriver::new()).unwrap();
+        let driver = BlackholeDriver::new();
+        run(input, &driver, None);
 
         assert_eq!(
             vec![(
@@ -132,7 +426,333 @@ mod tests {
                     working_dir.join("out.txt")
                 )
             )],
-            driver.log
+            driver.into_log()
+        );
+    }
+
+    #[test]
+    fn summarises_outcomes() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        fs::write(working_dir.join("same.txt"), "Hello, World!").unwrap();
+        let input = config(vec![
+            domain::Operation::Copy {
+                to: OperationPath::new(&working_dir, &working_dir, "same.txt"),
+                from: OperationPath::new(&working_dir, &working_dir, "in.txt"),
+            },
+            domain::Operation::Copy {
+                to: OperationPath::new(&working_dir, &working_dir, "out.txt"),
+                from: OperationPath::new(&working_dir, &working_dir, "in.txt"),
+            },
+        ]);
+
+        let summary = run(input, &BlackholeDriver::new(), None).summary;
+
+        assert_eq!(
+            Summary {
+                ok: 2,
+                changed: 1,
+                skipped: 0,
+                failed: 0,
+                ignored: 0,
+            },
+            summary
+        );
+        assert_eq!(
+            "2 ok, 1 changed, 0 skipped, 0 failed, 0 ignored",
+            summary.to_string()
+        );
+    }
+
+    #[test]
+    fn stops_at_the_first_failure() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        let input = config(vec![missing_copy(&working_dir), copy(&working_dir)]);
+
+        let report = run(input, &IoDriver::new(), None);
+
+        assert_eq!(1, report.summary.failed);
+        assert_eq!(0, report.summary.ok);
+        assert!(report.result().is_err());
+        assert!(!working_dir.join("copy.txt").exists());
+    }
+
+    #[test]
+    fn keeps_going_after_a_failure() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        let mut input = config(vec![missing_copy(&working_dir), copy(&working_dir)]);
+        input.keep_going = true;
+
+        let report = run(input, &IoDriver::new(), None);
+
+        assert_eq!(1, report.summary.failed);
+        assert_eq!(1, report.summary.ok);
+        assert!(report.result().is_err());
+        assert!(working_dir.join("copy.txt").exists());
+    }
+
+    struct PanickingDriver;
+
+    impl Driver for PanickingDriver {
+        fn copy(&self, _: &Path, _: &Path) -> Result<OperationOutcome, domain::Error> {
+            panic!("copy went wrong")
+        }
+
+        fn link(&self, _: &Path, _: &Path, _: bool) -> Result<OperationOutcome, domain::Error> {
+            panic!("link went wrong")
+        }
+
+        fn exec(&self, _: &str, _: &Exec) -> Result<OperationOutcome, domain::Error> {
+            panic!("exec went wrong")
+        }
+
+        fn shell(
+            &self,
+            _: &str,
+            _: &Path,
+            _: &OperationCommand,
+        ) -> Result<OperationOutcome, domain::Error> {
+            panic!("shell went wrong")
+        }
+
+        fn remove(&self, _: &Path) -> Result<OperationOutcome, domain::Error> {
+            panic!("remove went wrong")
+        }
+
+        fn restore(&self, _: &Path, _: &Path) -> Result<OperationOutcome, domain::Error> {
+            panic!("restore went wrong")
+        }
+
+        fn skip(&self, _: &str, _: &str, _: &str) {}
+    }
+
+    #[test]
+    fn operations_that_panic_fail() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let input = config(vec![copy(&working_dir), copy(&working_dir)]);
+
+        let report = run(input, &PanickingDriver, None);
+
+        assert_eq!(1, report.summary.failed);
+        assert_eq!(
+            "stopped unexpectedly: copy went wrong",
+            report.failures[0].error.to_string()
+        );
+    }
+
+    #[test]
+    fn ignores_errors() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        let mut input = config(vec![missing_copy(&working_dir), copy(&working_dir)]);
+        input.steps[0].ignore_errors = true;
+
+        let report = run(input, &IoDriver::new(), None);
+
+        assert_eq!(0, report.summary.failed);
+        assert_eq!(1, report.summary.ignored);
+        assert_eq!(1, report.summary.ok);
+        assert!(report.result().is_ok());
+    }
+
+    #[test]
+    fn only_runs_selected_steps() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        let mut input = config(vec![missing_copy(&working_dir), copy(&working_dir)]);
+        input.steps[0].tags = BTreeSet::from(["gui".into()]);
+        input.steps[1].tags = BTreeSet::from(["git".into()]);
+        input.selection.skip_tags = BTreeSet::from(["gui".into()]);
+
+        let driver = BlackholeDriver::new();
+        let report = run(input, &driver, None);
+
+        assert_eq!(
+            Summary {
+                ok: 1,
+                changed: 1,
+                skipped: 0,
+                failed: 0,
+                ignored: 0,
+            },
+            report.summary
+        );
+        assert_eq!(1, driver.into_log().len());
+    }
+
+    #[test]
+    fn skips_steps_that_need_a_failed_step() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        let mut input = config(vec![
+            missing_copy(&working_dir),
+            copy(&working_dir),
+            copy(&working_dir),
+            copy(&working_dir),
+        ]);
+        input.keep_going = true;
+        input.steps[1].needs = vec!["1".into()];
+        input.steps[2].needs = vec!["2".into()];
+
+        let report = run(input, &IoDriver::new(), None);
+
+        assert_eq!(
+            Summary {
+                ok: 1,
+                changed: 1,
+                skipped: 2,
+                failed: 1,
+                ignored: 0,
+            },
+            report.summary
+        );
+    }
+
+    fn bash(working_dir: &Path, script: &str) -> Operation {
+        Operation::Exec(Box::new(Exec::new(
+            working_dir,
+            "bash".into(),
+            vec!["-c".into(), script.into()],
+        )))
+    }
+
+    #[test]
+    fn runs_independent_steps_at_once() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let wait_for = |file: &str| {
+            format!(
+                "for _ in $(seq 100); do [ -e {} ] && exit 0; sleep 0.05; done; exit 1",
+                file
+            )
+        };
+        let mut input = config(vec![
+            bash(&working_dir, &format!("touch a; {}", wait_for("b"))),
+            bash(&working_dir, &format!("touch b; {}", wait_for("a"))),
+        ]);
+        input.jobs = 2;
+
+        let report = run(input, &IoDriver::new(), None);
+
+        assert_eq!(2, report.summary.ok);
+        assert!(report.result().is_ok());
+    }
+
+    #[test]
+    fn waits_for_needs_when_running_at_once() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut input = config(vec![
+            bash(&working_dir, "sleep 0.2; touch a"),
+            bash(&working_dir, "[ -e a ]"),
+            bash(&working_dir, "true"),
+        ]);
+        input.jobs = 3;
+        input.steps[1].needs = vec!["1".into()];
+
+        let report = run(input, &IoDriver::new(), None);
+
+        assert_eq!(3, report.summary.ok);
+        assert!(report.result().is_ok());
+    }
+
+    #[test]
+    fn reports_failures_in_order_when_running_at_once() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut input = config(vec![
+            bash(&working_dir, "sleep 0.2; exit 1"),
+            bash(&working_dir, "exit 1"),
+        ]);
+        input.jobs = 2;
+        input.keep_going = true;
+
+        let report = run(input, &IoDriver::new().with_grouped_output(), None);
+
+        assert_eq!(
+            vec!["1", "2"],
+            report
+                .failures
+                .iter()
+                .map(|failure| failure.label.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn records_successful_steps_in_the_state() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        let mut input = config(vec![copy(&working_dir), missing_copy(&working_dir)]);
+        input.keep_going = true;
+        let store = Store::open(&working_dir.join("state.json")).unwrap();
+
+        run(input, &IoDriver::new(), Some(&store));
+
+        let state = Store::open(&working_dir.join("state.json"))
+            .unwrap()
+            .state();
+        assert_eq!(
+            vec![Some(working_dir.join("copy.txt"))],
+            state
+                .entries
+                .into_iter()
+                .map(|entry| entry.target)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn skips_steps_whose_condition_is_false() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        let mut input = config(vec![missing_copy(&working_dir), copy(&working_dir)]);
+        input.steps[0].when = Some(When {
+            expression: "os == \"plan9\"".into(),
+            condition: Condition::Constant(false),
+        });
+
+        let driver = BlackholeDriver::new();
+        let report = run(input, &driver, None);
+
+        assert_eq!(
+            Summary {
+                ok: 1,
+                changed: 1,
+                skipped: 1,
+                failed: 0,
+                ignored: 0,
+            },
+            report.summary
+        );
+        assert_eq!(
+            (
+                String::from("copy"),
+                String::from("(skipped: when `os == \"plan9\"` is false)")
+            ),
+            driver.into_log()[0]
+        );
+    }
+
+    #[test]
+    fn failures_include_their_origin_causes_and_hint() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut input = config(vec![missing_copy(&working_dir)]);
+        input.steps[0].origin = Some(Origin {
+            file: working_dir.join("ellipsis.yml"),
+            index: 1,
+            position: Some(Position { line: 3, column: 3 }),
+        });
+
+        let report = run(input, &IoDriver::new(), None);
+
+        assert_eq!(
+            format!(
+                "[1] copy from `{missing}` to `{out}` failed\n    in todo entry 1 at {config}:3:3\n    caused by: No such file or directory (os error 2)\n    hint: the source `{missing}` does not exist, check the `from` path",
+                missing = working_dir.join("missing.txt").display(),
+                out = working_dir.join("out.txt").display(),
+                config = working_dir.join("ellipsis.yml").display(),
+            ),
+            report.failures[0].to_string()
         );
     }
 }
@@ -0,0 +1,210 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::domain::{Operation, Step};
+use crate::operations::inspect;
+use crate::state::State;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    /// The target is just as the operation would leave it
+    InSync,
+    /// There is nothing at the target
+    Missing,
+    /// Something other than what the operation makes is at the target
+    Conflict,
+    /// The target is a link, but to somewhere else
+    WrongLink,
+    /// The target has been changed since ellipsis copied it
+    Modified,
+    /// The source has been changed since ellipsis copied it
+    OutOfDate,
+}
+
+impl Status {
+    const fn colour(self) -> &'static str {
+        match self {
+            Self::InSync => "32",
+            Self::Missing | Self::OutOfDate => "33",
+            Self::Conflict | Self::WrongLink | Self::Modified => "31",
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::InSync => "in sync",
+            Self::Missing => "missing",
+            Self::Conflict => "conflict",
+            Self::WrongLink => "wrong link",
+            Self::Modified => "modified",
+            Self::OutOfDate => "out of date",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct TargetStatus {
+    pub name: String,
+    pub kind: String,
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub status: Status,
+}
+
+/// The status of the target of each copy and link, without changing
+/// anything
+pub fn status<'a, I>(steps: I, known: &State) -> Vec<TargetStatus>
+where
+    I: IntoIterator<Item = &'a Step>,
+{
+    steps
+        .into_iter()
+        .filter_map(|step| {
+            let (from, to, status) = match &step.operation {
+                Operation::Copy { from, to } => {
+                    let copied_hash = known
+                        .entries
+                        .iter()
+                        .find(|entry| entry.target.as_ref() == Some(&to.location))
+                        .and_then(|entry| entry.hash.as_deref());
+
+                    (
+                        from,
+                        to,
+                        inspect::copy_status(&from.location, &to.location, copied_hash),
+                    )
+                }
+                Operation::Link { from, to, .. } => {
+                    (from, to, inspect::link_status(&from.location, &to.location))
+                }
+                Operation::Exec(_) | Operation::Shell { .. } => return None,
+            };
+
+            Some(TargetStatus {
+                name: step.name.clone(),
+                kind: step.operation.kind().into(),
+                source: from.location.clone(),
+                target: to.location.clone(),
+                status,
+            })
+        })
+        .collect()
+}
+
+/// Line the statuses up in columns, colouring each status if asked to
+pub fn table(statuses: &[TargetStatus], colour: bool) -> String {
+    let status_width = statuses
+        .iter()
+        .map(|status| status.status.to_string().len())
+        .chain(["STATUS".len()])
+        .max()
+        .unwrap_or_default();
+    let name_width = statuses
+        .iter()
+        .map(|status| status.name.len())
+        .chain(["NAME".len()])
+        .max()
+        .unwrap_or_default();
+
+    let mut table = format!(
+        "{:status_width$}  {:4}  {:name_width$}  TARGET\n",
+        "STATUS", "KIND", "NAME"
+    );
+
+    for status in statuses {
+        let padded = format!("{:status_width$}", status.status);
+        let shown = if colour {
+            format!("\x1b[{}m{}\x1b[0m", status.status.colour(), padded)
+        } else {
+            padded
+        };
+
+        table.push_str(&format!(
+            "{}  {:4}  {:name_width$}  {}\n",
+            shown,
+            status.kind,
+            status.name,
+            status.target.display()
+        ));
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use indoc::indoc;
+
+    use super::{status, table, Status, TargetStatus};
+    use crate::domain::{Exec, Operation, OperationPath, Step};
+    use crate::state::State;
+
+    #[test]
+    fn only_copies_and_links_have_a_status() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        let path = |location: &str| OperationPath::new(&working_dir, &working_dir, location);
+        let steps = vec![
+            Step::new(
+                "copy",
+                Operation::Copy {
+                    from: path("in.txt"),
+                    to: path("in.txt"),
+                },
+            ),
+            Step::new(
+                "link",
+                Operation::Link {
+                    from: path("in.txt"),
+                    to: path("link.txt"),
+                    overwrite: false,
+                },
+            ),
+            Step::new(
+                "exec",
+                Operation::Exec(Box::new(Exec::new(&working_dir, "true".into(), vec![]))),
+            ),
+        ];
+
+        assert_eq!(
+            vec![Status::InSync, Status::Missing],
+            status(&steps, &State::default())
+                .into_iter()
+                .map(|status| status.status)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn table_of_statuses() {
+        let target_status = |name: &str, kind: &str, status| TargetStatus {
+            name: name.into(),
+            kind: kind.into(),
+            source: PathBuf::from("/dotfiles").join(name),
+            target: Path::new("/home/your-home").join(name),
+            status,
+        };
+        let statuses = vec![
+            target_status("vimrc", "link", Status::WrongLink),
+            target_status("gitconfig", "copy", Status::InSync),
+        ];
+
+        assert_eq!(
+            indoc! {"
+                STATUS      KIND  NAME       TARGET
+                wrong link  link  vimrc      /home/your-home/vimrc
+                in sync     copy  gitconfig  /home/your-home/gitconfig
+            "},
+            table(&statuses, false)
+        );
+        assert!(table(&statuses, true).contains("\x1b[31mwrong link\x1b[0m"));
+    }
+}
@@ -0,0 +1,517 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::domain;
+use crate::domain::{Driver, Exec, Operation, OperationOutcome, Step};
+use crate::operations::inspect;
+use crate::operations::runner::Report;
+use crate::state;
+use crate::state::{Entry, State, Store};
+
+/// Everything the steps copy or link to
+pub fn targets(steps: &[Step]) -> BTreeSet<PathBuf> {
+    steps
+        .iter()
+        .filter_map(|step| match &step.operation {
+            Operation::Copy { to, .. } | Operation::Link { to, .. } => Some(to.location.clone()),
+            Operation::Exec(_) | Operation::Shell { .. } => None,
+        })
+        .collect()
+}
+
+/// Remove the copies and links ellipsis made that are no longer wanted
+///
+/// Anything that has changed since ellipsis made it is left alone, and
+/// ellipsis stops keeping track of it unless it has a backup of what it
+/// replaced, so that backup is still put back by [`uninstall`].
+pub fn prune<T>(
+    wanted: &BTreeSet<PathBuf>,
+    known: &State,
+    driver: &T,
+    state: Option<&Store>,
+) -> Report
+where
+    T: Driver,
+{
+    undo(
+        known.entries.iter().filter(|entry| {
+            entry
+                .target
+                .as_ref()
+                .is_some_and(|target| !wanted.contains(target))
+        }),
+        driver,
+        state,
+    )
+}
+
+/// [`prune`] once a run has finished, unless the run failed, as the
+/// config has then only partly been applied
+pub fn prune_after<T>(
+    mut report: Report,
+    wanted: &BTreeSet<PathBuf>,
+    known: &State,
+    driver: &T,
+    state: Option<&Store>,
+) -> Report
+where
+    T: Driver,
+{
+    if report.result().is_ok() {
+        report.merge(prune(wanted, known, driver, state));
+    }
+
+    report
+}
+
+/// Reverse everything ellipsis has done, latest first
+///
+/// Copies and links are removed as for [`prune`], then whatever they
+/// replaced is put back. Commands are reversed by running their `undo`.
+pub fn uninstall<T>(known: &State, driver: &T, state: Option<&Store>) -> Report
+where
+    T: Driver,
+{
+    undo(known.entries.iter().rev(), driver, state)
+}
+
+fn undo<'a, I, T>(entries: I, driver: &T, state: Option<&Store>) -> Report
+where
+    I: Iterator<Item = &'a Entry>,
+    T: Driver,
+{
+    let mut report = Report::default();
+
+    for entry in entries {
+        match undo_entry(driver, state, entry) {
+            Ok(outcome) => report.summary.record(outcome),
+            Err(error) => report.fail(entry.name.clone(), None, error, false),
+        }
+    }
+
+    report
+}
+
+fn undo_entry<T>(
+    driver: &T,
+    state: Option<&Store>,
+    entry: &Entry,
+) -> Result<OperationOutcome, domain::Error>
+where
+    T: Driver,
+{
+    let outcome = match (&entry.target, &entry.undo) {
+        (Some(target), _) => undo_deployment(driver, entry, target)?,
+        (None, Some(undo)) => driver.exec(
+            &entry.name,
+            &Exec::new(&undo.working_dir, undo.command.clone(), undo.args.clone()),
+        )?,
+        (None, None) => {
+            driver.skip(&entry.name, &entry.kind, "it has no `undo`");
+            OperationOutcome::Skipped
+        }
+    };
+
+    let keeps_a_backup = outcome == OperationOutcome::Skipped
+        && entry
+            .backup
+            .as_deref()
+            .is_some_and(|backup| fs::symlink_metadata(backup).is_ok());
+
+    if let Some(state) = state.filter(|_| !keeps_a_backup) {
+        state.forget(entry).map_err(domain::Error::State)?;
+    }
+
+    Ok(outcome)
+}
+
+fn undo_deployment<T>(
+    driver: &T,
+    entry: &Entry,
+    target: &Path,
+) -> Result<OperationOutcome, domain::Error>
+where
+    T: Driver,
+{
+    let backup = entry
+        .backup
+        .as_deref()
+        .filter(|backup| fs::symlink_metadata(backup).is_ok());
+
+    let outcome = if fs::symlink_metadata(target).is_err() {
+        OperationOutcome::Unchanged
+    } else if is_still_ours(entry, target) {
+        driver.remove(target)?
+    } else {
+        let kept = backup.map_or_else(String::new, |backup| {
+            format!(", what it replaced is in `{}`", backup.display())
+        });
+        driver.skip(
+            &entry.name,
+            &entry.kind,
+            &format!(
+                "`{}` has changed since ellipsis made it{}",
+                target.display(),
+                kept
+            ),
+        );
+        return Ok(OperationOutcome::Skipped);
+    };
+
+    match backup {
+        Some(backup) => driver.restore(backup, target),
+        None => Ok(outcome),
+    }
+}
+
+/// Whether a target is still just as ellipsis made it
+pub(super) fn is_still_ours(entry: &Entry, target: &Path) -> bool {
+    match (entry.kind.as_str(), &entry.source) {
+        ("link", Some(source)) => inspect::is_link_to(source, target),
+        ("copy", _) => {
+            fs::symlink_metadata(target).is_ok_and(|metadata| metadata.is_file())
+                && entry.hash.is_some()
+                && state::hash(target) == entry.hash
+        }
+        _ => false,
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Dangling {
+    pub link: PathBuf,
+    pub target: PathBuf,
+}
+
+impl Display for Dangling {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "dangling link `{}` points to `{}`, which does not exist",
+            self.link.display(),
+            self.target.display()
+        )
+    }
+}
+
+/// Links in the given directories that point into the dotfiles directory,
+/// at something that isn't there any more
+pub fn dangling<'a, I>(dirs: I, dotfiles_dir: &Path) -> Vec<Dangling>
+where
+    I: IntoIterator<Item = &'a Path>,
+{
+    let mut dangling = Vec::new();
+
+    for dir in dirs.into_iter().collect::<BTreeSet<_>>() {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.filter_map(Result::ok) {
+            let link = entry.path();
+            let target = match fs::read_link(&link) {
+                Ok(target) => dir.join(target),
+                Err(_) => continue,
+            };
+
+            if target.starts_with(dotfiles_dir) && fs::metadata(&target).is_err() {
+                dangling.push(Dangling { link, target });
+            }
+        }
+    }
+
+    dangling.sort_by(|a, b| a.link.cmp(&b.link));
+    dangling
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::io;
+    use std::io::ErrorKind;
+    use std::os::unix::fs as unixfs;
+    use std::path::Path;
+    use std::time::SystemTime;
+
+    use super::{dangling, prune, prune_after, targets, uninstall, Dangling};
+    use crate::domain;
+    use crate::domain::{Operation, OperationPath, Step};
+    use crate::operations::runner::Report;
+    use crate::operations::{BlackholeDriver, IoDriver};
+    use crate::state::{Entry, State, Store, Undo};
+
+    fn step(operation: Operation) -> Step {
+        Step::new("dotfile", operation)
+    }
+
+    fn link(working_dir: &Path) -> Step {
+        step(Operation::Link {
+            from: OperationPath::new(working_dir, working_dir, "in.txt"),
+            to: OperationPath::new(working_dir, working_dir, "link.txt"),
+            overwrite: false,
+        })
+    }
+
+    fn copy(working_dir: &Path) -> Step {
+        step(Operation::Copy {
+            from: OperationPath::new(working_dir, working_dir, "in.txt"),
+            to: OperationPath::new(working_dir, working_dir, "copy.txt"),
+        })
+    }
+
+    fn deployed(working_dir: &Path) -> State {
+        fs::write(working_dir.join("in.txt"), "Hello, World!").unwrap();
+        fs::write(working_dir.join("copy.txt"), "Hello, World!").unwrap();
+        unixfs::symlink(working_dir.join("in.txt"), working_dir.join("link.txt")).unwrap();
+
+        State {
+            entries: vec![
+                Entry::new(&link(working_dir), SystemTime::now()),
+                Entry::new(&copy(working_dir), SystemTime::now()),
+            ],
+            ..State::default()
+        }
+    }
+
+    #[test]
+    fn keeps_what_is_still_wanted() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let known = deployed(&working_dir);
+
+        let report = prune(
+            &targets(&[link(&working_dir), copy(&working_dir)]),
+            &known,
+            &IoDriver::new(),
+            None,
+        );
+
+        assert_eq!(0, report.summary.changed);
+        assert!(working_dir.join("link.txt").exists());
+        assert!(working_dir.join("copy.txt").exists());
+    }
+
+    #[test]
+    fn removes_what_is_no_longer_wanted() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let known = deployed(&working_dir);
+        let store = Store::open(&working_dir.join("state.json")).unwrap();
+
+        let report = prune(&BTreeSet::new(), &known, &IoDriver::new(), Some(&store));
+
+        assert_eq!(2, report.summary.changed);
+        assert!(!working_dir.join("link.txt").exists());
+        assert!(!working_dir.join("copy.txt").exists());
+        assert!(working_dir.join("in.txt").exists());
+        assert_eq!(State::default(), store.state());
+    }
+
+    #[test]
+    fn does_not_prune_after_a_failed_run() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let known = deployed(&working_dir);
+        let mut failed = Report::default();
+        failed.fail(
+            "1".into(),
+            None,
+            domain::Error::Remove(working_dir.join("x"), io::Error::from(ErrorKind::NotFound)),
+            false,
+        );
+
+        let report = prune_after(failed, &BTreeSet::new(), &known, &IoDriver::new(), None);
+
+        assert_eq!(0, report.summary.changed);
+        assert_eq!(1, report.summary.failed);
+        assert!(working_dir.join("link.txt").exists());
+        assert!(working_dir.join("copy.txt").exists());
+    }
+
+    #[test]
+    fn prunes_after_a_run_that_succeeded() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let known = deployed(&working_dir);
+
+        let report = prune_after(
+            Report::default(),
+            &BTreeSet::new(),
+            &known,
+            &IoDriver::new(),
+            None,
+        );
+
+        assert_eq!(2, report.summary.changed);
+        assert!(!working_dir.join("copy.txt").exists());
+    }
+
+    #[test]
+    fn leaves_what_has_changed_alone() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let known = deployed(&working_dir);
+        fs::write(working_dir.join("copy.txt"), "Edited").unwrap();
+        fs::remove_file(working_dir.join("link.txt")).unwrap();
+        unixfs::symlink(working_dir.join("copy.txt"), working_dir.join("link.txt")).unwrap();
+
+        let report = prune(&BTreeSet::new(), &known, &IoDriver::new(), None);
+
+        assert_eq!(2, report.summary.skipped);
+        assert_eq!(
+            "Edited",
+            fs::read_to_string(working_dir.join("copy.txt")).unwrap()
+        );
+        assert!(working_dir.join("link.txt").exists());
+    }
+
+    #[test]
+    fn keeps_track_of_what_has_changed_while_it_has_a_backup() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let store = Store::open(&working_dir.join("state.json")).unwrap();
+        let mut known = deployed(&working_dir);
+        for entry in [link(&working_dir), copy(&working_dir)] {
+            store
+                .record(&entry, domain::OperationOutcome::Changed)
+                .unwrap();
+        }
+        fs::write(working_dir.join("backup.txt"), "I was here first").unwrap();
+        known.entries[1].backup = Some(working_dir.join("backup.txt"));
+        fs::write(working_dir.join("copy.txt"), "Edited").unwrap();
+        fs::remove_file(working_dir.join("link.txt")).unwrap();
+        fs::write(working_dir.join("link.txt"), "Mine now").unwrap();
+
+        let report = uninstall(&known, &IoDriver::new(), Some(&store));
+
+        assert_eq!(2, report.summary.skipped);
+        assert_eq!(
+            vec![Some(working_dir.join("copy.txt"))],
+            store
+                .state()
+                .entries
+                .iter()
+                .map(|entry| entry.target.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn dry_run_only_logs() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let known = deployed(&working_dir);
+        let driver = BlackholeDriver::new();
+
+        prune(&BTreeSet::new(), &known, &driver, None);
+
+        assert_eq!(
+            vec![
+                (
+                    String::from("remove"),
+                    format!("{:?}", working_dir.join("link.txt"))
+                ),
+                (
+                    String::from("remove"),
+                    format!("{:?}", working_dir.join("copy.txt"))
+                ),
+            ],
+            driver.into_log()
+        );
+        assert!(working_dir.join("link.txt").exists());
+    }
+
+    #[test]
+    fn uninstall_restores_what_was_replaced() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let mut known = deployed(&working_dir);
+        fs::write(working_dir.join("backup.txt"), "I was here first").unwrap();
+        known.entries[1].backup = Some(working_dir.join("backup.txt"));
+
+        let report = uninstall(&known, &IoDriver::new(), None);
+
+        assert_eq!(2, report.summary.changed);
+        assert!(!working_dir.join("link.txt").exists());
+        assert_eq!(
+            "I was here first",
+            fs::read_to_string(working_dir.join("copy.txt")).unwrap()
+        );
+    }
+
+    #[test]
+    fn uninstall_runs_undo_latest_first() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let undo = |word: &str| Undo {
+            working_dir: working_dir.clone(),
+            command: "bash".into(),
+            args: vec!["-c".into(), format!("echo {} >> undone.txt", word)],
+        };
+        let command = |name: &str, undo: Option<Undo>| Entry {
+            name: name.into(),
+            kind: "exec".into(),
+            target: None,
+            source: None,
+            hash: None,
+            backup: None,
+            undo,
+            applied_at: "2000-01-01T00:00:00Z".into(),
+        };
+        let known = State {
+            entries: vec![
+                command("first", Some(undo("first"))),
+                command("untouched", None),
+                command("second", Some(undo("second"))),
+            ],
+            ..State::default()
+        };
+
+        let report = uninstall(&known, &IoDriver::new(), None);
+
+        assert_eq!(2, report.summary.changed);
+        assert_eq!(1, report.summary.skipped);
+        assert_eq!(
+            "second\nfirst\n",
+            fs::read_to_string(working_dir.join("undone.txt")).unwrap()
+        );
+    }
+
+    #[test]
+    fn uninstall_runs_the_undo_of_a_shell() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let known = State {
+            entries: vec![Entry::new(
+                &step(Operation::new_shell(
+                    &working_dir,
+                    "true".into(),
+                    "bash".into(),
+                    Some("echo undone > undone.txt".into()),
+                )),
+                SystemTime::now(),
+            )],
+            ..State::default()
+        };
+
+        let report = uninstall(&known, &IoDriver::new(), None);
+
+        assert_eq!(1, report.summary.changed);
+        assert_eq!(
+            "undone\n",
+            fs::read_to_string(working_dir.join("undone.txt")).unwrap()
+        );
+    }
+
+    #[test]
+    fn finds_dangling_links_into_the_dotfiles() {
+        let dotfiles_dir = tempfile::tempdir().unwrap().into_path();
+        let home = tempfile::tempdir().unwrap().into_path();
+        fs::write(dotfiles_dir.join("kept.txt"), "Hello, World!").unwrap();
+        unixfs::symlink(dotfiles_dir.join("kept.txt"), home.join("kept.txt")).unwrap();
+        unixfs::symlink(dotfiles_dir.join("gone.txt"), home.join("gone.txt")).unwrap();
+        unixfs::symlink(home.join("elsewhere.txt"), home.join("elsewhere.txt")).unwrap();
+
+        assert_eq!(
+            vec![Dangling {
+                link: home.join("gone.txt"),
+                target: dotfiles_dir.join("gone.txt"),
+            }],
+            dangling([home.as_path()], &dotfiles_dir)
+        );
+    }
+}
@@ -0,0 +1,491 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error as ThisError;
+
+use crate::domain::{Operation, OperationOutcome, Step};
+
+const VERSION: u32 = 2;
+
+/// Where the state is kept, `$XDG_STATE_HOME/ellipsis/state.json`, falling
+/// back to `~/.local/state` when `XDG_STATE_HOME` isn't set
+pub fn location(home: &Path) -> PathBuf {
+    env::var_os("XDG_STATE_HOME")
+        .filter(|state_home| !state_home.is_empty())
+        .map_or_else(|| home.join(".local").join("state"), PathBuf::from)
+        .join("ellipsis")
+        .join("state.json")
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    pub version: u32,
+    #[serde(default)]
+    pub entries: Vec<Entry>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            entries: Vec::new(),
+        }
+    }
+}
+
+/// Something ellipsis has deployed. Copies and links are known by their
+/// target, commands by a hash of what they ran, so reordering or renaming
+/// steps doesn't mix them up.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undo: Option<Undo>,
+    pub applied_at: String,
+}
+
+/// The command that reverses an exec or shell
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Undo {
+    pub working_dir: PathBuf,
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+impl Entry {
+    pub fn new(step: &Step, applied_at: SystemTime) -> Self {
+        let (target, source) = match &step.operation {
+            Operation::Copy { from, to } | Operation::Link { from, to, .. } => {
+                (Some(to.location.clone()), Some(from.location.clone()))
+            }
+            Operation::Exec(_) | Operation::Shell { .. } => (None, None),
+        };
+        let undo = match &step.operation {
+            Operation::Exec(exec) => exec.undo.as_ref().map(|undo| Undo {
+                working_dir: exec.working_dir.clone(),
+                command: undo.command.clone(),
+                args: undo.args.clone(),
+            }),
+            Operation::Shell {
+                working_dir,
+                command,
+            } => command.undo.as_ref().map(|undo| Undo {
+                working_dir: working_dir.clone(),
+                command: command.shell.clone(),
+                args: vec!["-c".into(), undo.clone()],
+            }),
+            Operation::Copy { .. } | Operation::Link { .. } => None,
+        };
+
+        Self {
+            name: step.name.clone(),
+            kind: step.operation.kind().into(),
+            hash: match &step.operation {
+                Operation::Exec(exec) => Some(command_hash(
+                    &iter::once(&exec.command)
+                        .chain(&exec.args)
+                        .map(String::as_str)
+                        .collect::<Vec<_>>(),
+                )),
+                Operation::Shell { command, .. } => {
+                    Some(command_hash(&[&command.shell, "-c", &command.run]))
+                }
+                Operation::Copy { .. } | Operation::Link { .. } => target.as_deref().and_then(hash),
+            },
+            target,
+            source,
+            backup: None,
+            undo,
+            applied_at: humantime::format_rfc3339_seconds(applied_at).to_string(),
+        }
+    }
+
+    fn is_same_as(&self, other: &Self) -> bool {
+        match (&self.target, &other.target) {
+            (Some(target), Some(other_target)) => target == other_target,
+            (None, None) => {
+                self.kind == other.kind
+                    && match (&self.hash, &other.hash) {
+                        (Some(hash), Some(other_hash)) => hash == other_hash,
+                        // Version 1 didn't hash commands, so only had the name
+                        _ => self.name == other.name,
+                    }
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Where the backup of something at `path` goes, mirroring its location
+/// under the backups directory
+pub fn backup_path(backups_dir: &Path, path: &Path) -> PathBuf {
+    backups_dir.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+/// The sha256 of a file's contents, following links, or `None` if it isn't
+/// a readable file
+pub fn hash(path: &Path) -> Option<String> {
+    if !path.is_file() {
+        return None;
+    }
+
+    fs::read(path)
+        .ok()
+        .map(|contents| format!("{:x}", Sha256::digest(contents)))
+}
+
+/// The sha256 of a command and its arguments
+fn command_hash(command: &[&str]) -> String {
+    format!("{:x}", Sha256::digest(command.join("\0")))
+}
+
+/// The state on disk, kept up to date as operations are recorded
+#[derive(Debug)]
+pub struct Store {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let mut state: State = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|error| Error::Parse(path.to_path_buf(), error))?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => State::default(),
+            Err(error) => return Err(Error::Read(path.to_path_buf(), error)),
+        };
+
+        if state.version > VERSION {
+            return Err(Error::Version(path.to_path_buf(), state.version));
+        }
+        state.version = VERSION;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Where anything a copy or link replaced is kept, beside the state
+    pub fn backups_dir(&self) -> PathBuf {
+        self.path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("backups")
+    }
+
+    pub fn state(&self) -> State {
+        self.state.lock().expect("state poisoned").clone()
+    }
+
+    /// Record a step that completed with the given outcome, then write the
+    /// whole state out again
+    ///
+    /// Only steps that changed something are recorded. Something that was
+    /// already in place may be the user's own, so ellipsis doesn't take it
+    /// over, and if ellipsis made it earlier its original time is kept.
+    pub fn record(&self, step: &Step, outcome: OperationOutcome) -> Result<(), Error> {
+        if outcome != OperationOutcome::Changed {
+            return Ok(());
+        }
+
+        let mut entry = Entry::new(step, SystemTime::now());
+        entry.backup = entry
+            .target
+            .as_deref()
+            .map(|target| backup_path(&self.backups_dir(), target))
+            .filter(|backup| fs::symlink_metadata(backup).is_ok());
+        let mut state = self.state.lock().expect("state poisoned");
+
+        match state
+            .entries
+            .iter_mut()
+            .find(|known| known.is_same_as(&entry))
+        {
+            Some(known) => *known = entry,
+            None => state.entries.push(entry),
+        }
+
+        write(&self.path, &state)
+    }
+
+    /// Stop keeping track of something, then write the whole state out again
+    pub fn forget(&self, entry: &Entry) -> Result<(), Error> {
+        let mut state = self.state.lock().expect("state poisoned");
+        state.entries.retain(|known| !known.is_same_as(entry));
+
+        write(&self.path, &state)
+    }
+}
+
+/// Write the state to a temporary file beside the real one and rename it
+/// into place, so a crash part way through never leaves half a file behind
+fn write(path: &Path, state: &State) -> Result<(), Error> {
+    let write_error = |error| Error::Write(path.to_path_buf(), error);
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+
+    fs::create_dir_all(parent).map_err(write_error)?;
+
+    let mut file = tempfile::NamedTempFile::new_in(parent).map_err(write_error)?;
+    serde_json::to_writer_pretty(&mut file, state)
+        .map_err(io::Error::from)
+        .map_err(write_error)?;
+    writeln!(file).map_err(write_error)?;
+    file.persist(path)
+        .map_err(|error| write_error(error.error))?;
+
+    Ok(())
+}
+
+#[derive(ThisError, Debug)]
+pub enum Error {
+    #[error("could not read the state file `{0}`")]
+    Read(PathBuf, #[source] io::Error),
+    #[error("could not parse the state file `{0}`")]
+    Parse(PathBuf, #[source] serde_json::Error),
+    #[error("could not write the state file `{0}`")]
+    Write(PathBuf, #[source] io::Error),
+    #[error("the state file `{0}` is version {1}, which is newer than this ellipsis understands")]
+    Version(PathBuf, u32),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    use sha2::{Digest, Sha256};
+
+    use super::{backup_path, hash, Entry, Error, State, Store};
+    use crate::domain::{Exec, Operation, OperationOutcome, OperationPath, Step};
+
+    fn copy(working_dir: &Path) -> Step {
+        Step::new(
+            "dotfile",
+            Operation::Copy {
+                from: OperationPath::new(working_dir, working_dir, "in.txt"),
+                to: OperationPath::new(working_dir, working_dir, "out.txt"),
+            },
+        )
+    }
+
+    #[test]
+    fn entry_for_a_copy() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("out.txt"), "Hello, World!").unwrap();
+
+        assert_eq!(
+            Entry {
+                name: "dotfile".into(),
+                kind: "copy".into(),
+                target: Some(working_dir.join("out.txt")),
+                source: Some(working_dir.join("in.txt")),
+                hash: Some(
+                    "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f".into()
+                ),
+                backup: None,
+                undo: None,
+                applied_at: "1970-01-01T00:01:40Z".into(),
+            },
+            Entry::new(
+                &copy(&working_dir),
+                SystemTime::UNIX_EPOCH + Duration::from_secs(100)
+            )
+        );
+    }
+
+    #[test]
+    fn entry_for_a_command() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let entry = Entry::new(
+            &Step::new(
+                "install",
+                Operation::Exec(Box::new(Exec::new(&working_dir, "true".into(), vec![]))),
+            ),
+            SystemTime::UNIX_EPOCH,
+        );
+
+        assert_eq!("exec", entry.kind);
+        assert_eq!(None, entry.target);
+        assert_eq!(Some(format!("{:x}", Sha256::digest("true"))), entry.hash);
+    }
+
+    #[test]
+    fn commands_are_known_by_what_they_run() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let store = Store::open(&working_dir.join("state.json")).unwrap();
+        let exec = |name: &str, command: &str| {
+            Step::new(
+                name,
+                Operation::Exec(Box::new(Exec::new(&working_dir, command.into(), vec![]))),
+            )
+        };
+
+        store
+            .record(&exec("1", "true"), OperationOutcome::Changed)
+            .unwrap();
+        store
+            .record(&exec("2", "false"), OperationOutcome::Changed)
+            .unwrap();
+        store
+            .record(&exec("1", "false"), OperationOutcome::Changed)
+            .unwrap();
+
+        assert_eq!(
+            vec![("1", "exec"), ("1", "exec")],
+            store
+                .state()
+                .entries
+                .iter()
+                .map(|entry| (entry.name.as_str(), entry.kind.as_str()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn no_hash_for_a_directory() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+
+        assert_eq!(None, hash(&working_dir));
+    }
+
+    #[test]
+    fn missing_state_is_empty() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let store = Store::open(&working_dir.join("state.json")).unwrap();
+
+        assert_eq!(State::default(), store.state());
+    }
+
+    #[test]
+    fn records_and_reopens() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("out.txt"), "Hello, World!").unwrap();
+        let path = working_dir.join("deep").join("state.json");
+
+        let store = Store::open(&path).unwrap();
+        store
+            .record(&copy(&working_dir), OperationOutcome::Changed)
+            .unwrap();
+        fs::write(working_dir.join("out.txt"), "Hello, again!").unwrap();
+        store
+            .record(&copy(&working_dir), OperationOutcome::Changed)
+            .unwrap();
+
+        let state = Store::open(&path).unwrap().state();
+        assert_eq!(1, state.entries.len());
+        assert_eq!(hash(&working_dir.join("out.txt")), state.entries[0].hash);
+    }
+
+    #[test]
+    fn records_the_backup_of_what_was_replaced() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("out.txt"), "Hello, World!").unwrap();
+        let store = Store::open(&working_dir.join("state.json")).unwrap();
+        let backup = backup_path(&store.backups_dir(), &working_dir.join("out.txt"));
+        fs::create_dir_all(backup.parent().unwrap()).unwrap();
+        fs::write(&backup, "I exist").unwrap();
+
+        store
+            .record(&copy(&working_dir), OperationOutcome::Changed)
+            .unwrap();
+
+        assert_eq!(Some(backup), store.state().entries[0].backup);
+    }
+
+    #[test]
+    fn skipped_steps_are_not_recorded() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        let path = working_dir.join("state.json");
+
+        Store::open(&path)
+            .unwrap()
+            .record(&copy(&working_dir), OperationOutcome::Skipped)
+            .unwrap();
+
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn unchanged_steps_keep_their_time() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("out.txt"), "Hello, World!").unwrap();
+        let path = working_dir.join("state.json");
+        let mut known = Entry::new(&copy(&working_dir), SystemTime::UNIX_EPOCH);
+        known.applied_at = "2000-01-01T00:00:00Z".into();
+        fs::write(
+            &path,
+            serde_json::to_string(&State {
+                entries: vec![known.clone()],
+                ..State::default()
+            })
+            .unwrap(),
+        )
+        .unwrap();
+
+        let store = Store::open(&path).unwrap();
+        store
+            .record(&copy(&working_dir), OperationOutcome::Unchanged)
+            .unwrap();
+
+        assert_eq!(vec![known], store.state().entries);
+    }
+
+    #[test]
+    fn unchanged_steps_that_are_not_known_are_not_recorded() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("out.txt"), "Hello, World!").unwrap();
+        let path = working_dir.join("state.json");
+
+        Store::open(&path)
+            .unwrap()
+            .record(&copy(&working_dir), OperationOutcome::Unchanged)
+            .unwrap();
+
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn state_from_a_newer_ellipsis() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(
+            working_dir.join("state.json"),
+            r#"{"version": 3, "entries": []}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            Store::open(&working_dir.join("state.json")),
+            Err(Error::Version(_, 3))
+        ));
+    }
+
+    #[test]
+    fn corrupt_state() {
+        let working_dir = tempfile::tempdir().unwrap().into_path();
+        fs::write(working_dir.join("state.json"), "{").unwrap();
+
+        assert!(matches!(
+            Store::open(&working_dir.join("state.json")),
+            Err(Error::Parse(_, _))
+        ));
+    }
+}